@@ -1,4 +1,10 @@
 use std::rc::Rc;
+use std::f32::consts::PI;
+use na::{Vec2, Rot2, Rotation};
+use color::Color;
+use easing::Easing;
+use math;
+use event::Event;
 use timer::Ms;
 use timer::Timer;
 
@@ -18,6 +24,9 @@ pub enum State<T> {
     Function(Timer, Rc<Fn(&mut T, &mut Timer) -> Return<T>>),
     Series(List<T>),
     Parallel(List<T>),
+    Delay(Ms),
+    // Sends an event once it is reached.
+    Notify(Rc<Fn() -> Event>),
 }
 
 
@@ -31,6 +40,8 @@ impl<T> Clone for State<T> {
             Function(ref a, ref b) => Function(a.clone(), b.clone()),
             Series(ref a) => Series(a.clone()),
             Parallel(ref a) => Parallel(a.clone()),
+            Delay(ref a) => Delay(a.clone()),
+            Notify(ref a) => Notify(a.clone()),
         }
     }
 }
@@ -46,6 +57,17 @@ pub fn series<T>(mut list: List<T>) -> State<T> {
     State::Series(list)
 }
 
+
+pub fn delay<T>(ms: Ms) -> State<T> {
+    State::Delay(ms)
+}
+
+
+pub fn on_complete<T, F>(state: State<T>, f: F) -> State<T>
+        where F: Fn() -> Event + 'static {
+    series(vec![state, State::Notify(Rc::new(f))])
+}
+
 #[macro_export]
 macro_rules! repeat (
     ($state: expr) => {
@@ -81,12 +103,14 @@ macro_rules! if_out {
 
 
 impl<T> State<T> {
-    pub fn transition(&mut self, data: &mut T, delta: Ms) -> Return<T> {
+    pub fn transition(&mut self, data: &mut T, delta: Ms, events: &mut Vec<Event>)
+        -> Return<T>
+    {
         use self::State::*;
 
         macro_rules! next (
             ($s: expr) => (
-                if let Return::Become(x) = $s.transition(data, delta) {
+                if let Return::Become(x) = $s.transition(data, delta, events) {
                     $s = x;
                 }
             )
@@ -131,7 +155,101 @@ impl<T> State<T> {
                 timer.update(delta);
                 return function(data, timer)
             }
+
+            Delay(ref mut ms) => {
+                if delta >= *ms { return stop }
+                *ms -= delta;
+            }
+
+            Notify(ref f) => {
+                events.push(f());
+                return stop
+            }
         }
         Return::Remain
     }
+
+    // Jump to the end, as if all the time has passed.
+    pub fn finish(&mut self, data: &mut T, events: &mut Vec<Event>) {
+        use self::State::*;
+
+        match *self {
+            Nil | Delay(_) => {}
+            Series(ref mut list) => {
+                while let Some(mut state) = list.pop() {
+                    state.finish(data, events);
+                }
+            }
+            Parallel(ref mut list) => {
+                for state in list {
+                    state.finish(data, events);
+                }
+            }
+            // Only the running round, forever would never finish.
+            Repeat(_, _, ref mut state) => state.finish(data, events),
+            Function(ref mut timer, ref function) => {
+                timer.delta = 0;
+                timer.now = timer.total + 1;
+                let _ = function(data, timer);
+            }
+            Notify(ref f) => events.push(f()),
+        }
+        *self = Nil;
+    }
+
+    // Stop where it is.
+    pub fn cancel(&mut self) {
+        *self = State::Nil;
+    }
+}
+
+
+pub trait Interpolate: Copy {
+    fn interpolate(a: Self, b: Self, t: f32) -> Self;
+}
+
+
+impl Interpolate for f32 {
+    fn interpolate(a: f32, b: f32, t: f32) -> f32 {
+        math::linear(a, b, t)
+    }
+}
+
+
+impl Interpolate for Vec2<f32> {
+    fn interpolate(a: Vec2<f32>, b: Vec2<f32>, t: f32) -> Vec2<f32> {
+        math::linear(a, b, t)
+    }
+}
+
+
+impl Interpolate for Color {
+    fn interpolate(a: Color, b: Color, t: f32) -> Color {
+        Color::new(math::linear(a.r, b.r, t), math::linear(a.g, b.g, t),
+                   math::linear(a.b, b.b, t), math::linear(a.a, b.a, t))
+    }
+}
+
+
+impl Interpolate for Rot2<f32> {
+    // Turns the shortest way round.
+    fn interpolate(a: Rot2<f32>, b: Rot2<f32>, t: f32) -> Rot2<f32> {
+        let (a, b) = (a.rotation().x, b.rotation().x);
+        let mut delta = (b - a) % (PI * 2.0);
+        if delta > PI { delta -= PI * 2.0 }
+        if delta < -PI { delta += PI * 2.0 }
+        Rot2::new(na![a + delta * t])
+    }
+}
+
+
+// Animate the value `lens` points to inside `T`.
+pub fn tween<T, V, L>(ms: Ms, lens: L, from: V, to: V, easing: Easing) -> State<T>
+        where V: Interpolate + 'static, L: Fn(&mut T) -> &mut V + 'static {
+    function!(Timer::new(ms), move |data, timer| {
+        if_out!(timer,
+            { *lens(data) = to }
+            { *lens(data) = V::interpolate(from, to, easing.apply(timer.ratio())) }
+        )
+    })
 }
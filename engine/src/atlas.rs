@@ -0,0 +1,238 @@
+use na;
+use na::Vec2;
+use std::rc::Rc;
+use std::collections::BTreeSet;
+use glium::Display;
+use id::Id;
+use canvas::Canvas;
+use sprite::Sprite;
+use texture::Texture;
+
+
+// Around each region, so that linear filtering does not bleed a neighbour
+// or the edge of the page in.
+const PADDING: usize = 1;
+
+
+// Where a canvas went in the atlas, in pixels from the top left of its page.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Region {
+    pub id: Id,
+    pub page: usize,
+    pub offset: Vec2<i32>,
+    pub size: Vec2<i32>,
+    hidpi_factor: f32,
+}
+
+
+// Bottom-left skyline packer: the top of the packed rectangles is kept as
+// segments `(x, y, width)`, a rectangle goes where its bottom ends up highest.
+struct Skyline {
+    width: usize,
+    height: usize,
+    segments: Vec<(usize, usize, usize)>,
+}
+
+
+impl Skyline {
+    fn new(width: usize, height: usize) -> Skyline {
+        Skyline { width: width, height: height, segments: vec![(0, 0, width)] }
+    }
+
+    // Top of the rectangle if placed at segment `i`.
+    fn fits(&self, i: usize, width: usize, height: usize) -> Option<usize> {
+        let x = self.segments[i].0;
+        if x + width > self.width { return None }
+        let mut y = 0;
+        let mut left = width;
+        let mut j = i;
+        while left > 0 {
+            let (_, top, w) = self.segments[j];
+            y = ::std::cmp::max(y, top);
+            if y + height > self.height { return None }
+            left = left.saturating_sub(w);
+            j += 1;
+        }
+        Some (y)
+    }
+
+    fn insert(&mut self, width: usize, height: usize) -> Option<(usize, usize)> {
+        let mut best: Option<(usize, usize, usize)> = None;
+        for i in 0..self.segments.len() {
+            if let Some(y) = self.fits(i, width, height) {
+                let x = self.segments[i].0;
+                let better = match best {
+                    Some ((_, bx, by)) => y < by || (y == by && x < bx),
+                    None => true,
+                };
+                if better { best = Some ((i, x, y)) }
+            }
+        }
+        let (i, x, y) = match best {
+            Some (x) => x,
+            None => return None,
+        };
+        self.segments.insert(i, (x, y + height, width));
+        // Cut the segments now under the new one.
+        let end = x + width;
+        let mut j = i + 1;
+        while j < self.segments.len() {
+            let (sx, sy, sw) = self.segments[j];
+            if sx >= end { break }
+            if sx + sw <= end {
+                self.segments.remove(j);
+            }
+            else {
+                self.segments[j] = (end, sy, sx + sw - end);
+                break;
+            }
+        }
+        // Merge neighbours at the same height.
+        let mut j = 0;
+        while j + 1 < self.segments.len() {
+            let (ax, ay, aw) = self.segments[j];
+            let (_, by, bw) = self.segments[j + 1];
+            if ay == by {
+                self.segments[j] = (ax, ay, aw + bw);
+                self.segments.remove(j + 1);
+            }
+            else {
+                j += 1;
+            }
+        }
+        Some ((x, y))
+    }
+}
+
+
+struct Page {
+    texture: Rc<Texture>,
+    skyline: Skyline,
+    regions: BTreeSet<Id>,
+}
+
+
+// Canvases packed into shared textures, so that the sprites made from them
+// batch together instead of each having its own texture.
+pub struct Atlas {
+    size: usize,
+    pages: Vec<Page>,
+}
+
+
+impl Atlas {
+    // Pages of `size` by `size` pixels, added when the last one is full.
+    pub fn new(size: usize) -> Atlas {
+        Atlas { size: size, pages: Vec::new() }
+    }
+
+    fn add_page(&mut self, display: &Display) -> usize {
+        let size = self.size as u32;
+        let texture = Texture::empty(display, size, size).unwrap();
+        self.pages.push(Page {
+            texture: Rc::new(texture),
+            skyline: Skyline::new(self.size, self.size),
+            regions: BTreeSet::new(),
+        });
+        self.pages.len() - 1
+    }
+
+    // `None` if the canvas does not fit in a page.
+    pub fn insert(&mut self, display: &Display, canvas: Canvas) -> Option<Region> {
+        if !self.fits(&canvas) { return None }
+        let (width, height) = (canvas.width + 2 * PADDING, canvas.height + 2 * PADDING);
+        let mut found = None;
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            if let Some(x) = page.skyline.insert(width, height) {
+                found = Some ((i, x));
+                break;
+            }
+        }
+        let (page, (x, y)) = match found {
+            Some (x) => x,
+            None => {
+                let page = self.add_page(display);
+                let x = self.pages[page].skyline.insert(width, height).unwrap();
+                (page, x)
+            }
+        };
+        let (x, y) = (x + PADDING, y + PADDING);
+        let region = Region {
+            id: Id::new(),
+            page: page,
+            offset: na![x as i32, y as i32],
+            size: na![canvas.width as i32, canvas.height as i32],
+            hidpi_factor: canvas.hidpi_factor(),
+        };
+        let page = &mut self.pages[page];
+        page.texture.write((x as u32, y as u32),
+                           (canvas.width as u32, canvas.height as u32),
+                           canvas.buffer().clone());
+        page.regions.insert(region.id);
+        Some (region)
+    }
+
+    // Like `Canvas::into_sprite`, sharing the texture of the page.
+    pub fn sprite(&self, region: &Region) -> Sprite {
+        let size: Vec2<f32> = na::cast(region.size);
+        let size = na::cast(size / region.hidpi_factor);
+        Sprite::new(size, region.size, self.pages[region.page].texture.clone())
+            .offset(region.offset)
+    }
+
+    // Its space is only reused once its whole page is free, sprites of it
+    // keep drawing until then.
+    pub fn free(&mut self, region: &Region) {
+        let size = self.size;
+        if let Some(page) = self.pages.get_mut(region.page) {
+            page.regions.remove(&region.id);
+            if page.regions.is_empty() {
+                page.skyline = Skyline::new(size, size);
+            }
+        }
+    }
+
+    // Not bigger than a page, with its padding.
+    pub fn fits(&self, canvas: &Canvas) -> bool {
+        canvas.width + 2 * PADDING <= self.size && canvas.height + 2 * PADDING <= self.size
+    }
+
+    pub fn pages(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Skyline;
+
+    #[test]
+    fn placement() {
+        let mut skyline = Skyline::new(10, 10);
+        assert_eq!(skyline.insert(4, 3), Some ((0, 0)));
+        assert_eq!(skyline.insert(4, 5), Some ((4, 0)));
+        assert_eq!(skyline.insert(2, 2), Some ((8, 0)));
+        // On the lowest segment wide enough, then across several, the leftmost first.
+        assert_eq!(skyline.insert(4, 1), Some ((0, 3)));
+        assert_eq!(skyline.insert(6, 1), Some ((0, 5)));
+        assert_eq!(skyline.segments, vec![(0, 6, 6), (6, 5, 2), (8, 2, 2)]);
+    }
+
+    #[test]
+    fn merge() {
+        let mut skyline = Skyline::new(10, 10);
+        assert_eq!(skyline.insert(5, 2), Some ((0, 0)));
+        assert_eq!(skyline.insert(5, 2), Some ((5, 0)));
+        assert_eq!(skyline.segments, vec![(0, 2, 10)]);
+    }
+
+    #[test]
+    fn full_page() {
+        let mut skyline = Skyline::new(4, 4);
+        assert_eq!(skyline.insert(5, 1), None);
+        assert_eq!(skyline.insert(1, 5), None);
+        assert_eq!(skyline.insert(4, 4), Some ((0, 0)));
+        assert_eq!(skyline.insert(1, 1), None);
+    }
+}
@@ -0,0 +1,25 @@
+// Bundles a directory into one pack file, to be mounted with `vfs::Pack`.
+//
+//     pack assets assets.pack
+extern crate engine;
+
+use std::env;
+use std::path::Path;
+use std::process;
+use engine::vfs::write_pack;
+
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        println!("usage: {} <directory> <pack>", args[0]);
+        process::exit(1);
+    }
+    match write_pack(Path::new(&args[1]), Path::new(&args[2])) {
+        Ok (n) => println!("{} files packed into {}", n, args[2]),
+        Err (e) => {
+            println!("cannot pack {}: {}", args[1], e);
+            process::exit(1);
+        }
+    }
+}
@@ -1,14 +1,19 @@
 use na::{Diag, Vec2, zero};
-use math::{Mat, translation, linear};
+use math::{Mat, translation};
 use timer::Ms;
-use animation::{Return, State};
-use timer::Timer;
+use animation::{State, tween};
+use easing::Easing;
+use event::{Event, Update, EventStream};
+use render::Renderer;
+use timeline::{Animatable, Lens};
 
 use glium::Display;
 
 pub struct Camera<'display> {
     display: &'display Display,
     offset: Vec2<f32>,
+    // Offset before the last update, see `interpolated`.
+    previous: Vec2<f32>,
     state: State<Camera<'display>>,
 }
 
@@ -19,21 +24,39 @@ impl<'display> Camera<'display> {
         Camera {
             display: display,
             offset: ::na::zero(),
+            previous: ::na::zero(),
             state: State::Nil,
         }
     }
 
-    pub fn move_(&mut self, time: Ms, offset: Vec2<f32>) {
+    pub fn move_(&mut self, time: Ms, offset: Vec2<f32>, easing: Easing) {
         let current = self.offset;
-        let state: State<Camera> = function!(Timer::new(time), move |camera, timer| {
-            camera.offset = current + linear(zero(), offset, timer.ratio());
-            Return::Remain
-        });
+        self.state = tween(time, |camera: &mut Camera<'display>| &mut camera.offset,
+                           current, current + offset, easing);
+    }
+
+    pub fn set_state(&mut self, state: State<Camera<'display>>) {
         self.state = state;
     }
 
+    pub fn move_to(&mut self, time: Ms, target: Vec2<f32>, easing: Easing) {
+        let offset = target - self.offset;
+        self.move_(time, offset, easing);
+    }
+
+    pub fn look_at(&mut self, target: Vec2<f32>) {
+        self.state = State::Nil;
+        self.offset = target;
+        self.previous = target;
+    }
+
+    pub fn offset(&self) -> Vec2<f32> {
+        self.offset
+    }
+
     pub fn reset(&mut self) {
         self.offset = zero();
+        self.previous = zero();
     }
 
     pub fn right_top(&self) -> Vec2<f32> {
@@ -58,22 +81,68 @@ impl<'display> Camera<'display> {
 
 
     pub fn matrix(&self) -> Mat {
+        self.matrix_at(self.offset)
+    }
+
+    // Between the two last updates, `alpha` from `ProgramTimer::alpha`.
+    pub fn interpolated(&self, alpha: f32) -> Mat {
+        self.matrix_at(self.previous + (self.offset - self.previous) * alpha)
+    }
+
+    fn matrix_at(&self, offset: Vec2<f32>) -> Mat {
         let factor = self.display.get_window().unwrap().hidpi_factor();
         let (w, h) = self.display.get_framebuffer_dimensions();
         let (w, h) = (w as f32, h as f32);
         let f = factor * 2.0;
-        Mat::from_diag(&na![f/w, f/h, -1.0, 1.0]) * translation(-self.offset)
+        Mat::from_diag(&na![f/w, f/h, -1.0, 1.0]) * translation(-offset)
     }
 
-    pub fn update(&mut self, delta: Ms) {
+    pub fn finish(&mut self) -> Vec<Event> {
+        use std::mem::swap;
+
+        let mut events = Vec::new();
+        let mut state = State::Nil;
+        swap(&mut self.state, &mut state);
+        state.finish(self, &mut events);
+        events
+    }
+
+    pub fn cancel(&mut self) {
+        self.state.cancel();
+    }
+}
+
+
+impl<'display> Animatable for Camera<'display> {
+    fn lens(property: &str) -> Option<Lens<Camera<'display>>> {
+        fn offset<'a, 'b>(x: &'a mut Camera<'b>) -> &'a mut Vec2<f32> { &mut x.offset }
+
+        match property {
+            "offset" => Some (Lens::Vector(offset)),
+            _ => None,
+        }
+    }
+}
+
+
+impl<'display> Update for Camera<'display> {
+    fn update(&mut self, _: &Renderer, delta: Ms, mut stream: EventStream)
+        -> EventStream
+    {
         use std::mem::swap;
         use animation::Return;
 
+        self.previous = self.offset;
+        let mut events = Vec::new();
         let mut state = State::Nil;
         swap(&mut self.state, &mut state);
-        if let Return::Become(x) = state.transition(self, delta) {
+        if let Return::Become(x) = state.transition(self, delta, &mut events) {
             state = x;
         }
         self.state = state;
+        for event in events {
+            stream.push(event);
+        }
+        return stream;
     }
 }
@@ -1,6 +1,9 @@
 use na;
+use image;
 use na::Vec2;
+use std::io;
 use std::rc::Rc;
+use std::path::Path;
 use std::borrow::Cow;
 use glium::Display;
 use color::Color;
@@ -12,6 +15,18 @@ use glium::texture::{RawImage2d, PixelValue};
 pub type Buffer = Vec<Color>;
 
 
+fn from_rgba(pixel: &image::Rgba<u8>) -> Color {
+    let c = pixel.data;
+    Color::new(c[0] as f32 / 255.0, c[1] as f32 / 255.0, c[2] as f32 / 255.0, c[3] as f32 / 255.0)
+}
+
+
+fn to_rgba(color: Color) -> image::Rgba<u8> {
+    let byte = |x: f32| (x.max(0.0).min(1.0) * 255.0).round() as u8;
+    image::Rgba([byte(color.r), byte(color.g), byte(color.b), byte(color.a)])
+}
+
+
 #[derive(Clone)]
 pub struct Canvas {
     pub width: usize,
@@ -43,10 +58,41 @@ impl Canvas {
         }
     }
 
+    // Images are stored top row first, the canvas bottom row first.
+    pub fn from_image(image: &image::DynamicImage) -> Canvas {
+        let image = image.to_rgba();
+        let (width, height) = image.dimensions();
+        let mut canvas = Canvas::new(width as usize, height as usize);
+        for (x, y, pixel) in image.enumerate_pixels() {
+            canvas.set(x as i32, y as i32, from_rgba(pixel));
+        }
+        canvas
+    }
+
+    pub fn to_image(&self) -> image::RgbaImage {
+        image::ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+            to_rgba(self.get(x as i32, y as i32).unwrap())
+        })
+    }
+
+    // Rounded to 8 bits a channel.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.to_image().save(path.as_ref())
+    }
+
     pub fn factor(self, f: f32) -> Canvas {
         Canvas { hidpi_factor: f, ..self }
     }
 
+    pub fn hidpi_factor(&self) -> f32 {
+        self.hidpi_factor
+    }
+
+    // Bottom row first, as uploaded.
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
     pub fn into_sprite(self, display: &Display) -> Sprite {
         let texture = Texture::new(display, RawImage2d {
             data: Cow::Owned(self.buffer),
@@ -72,3 +118,406 @@ impl Canvas {
         &mut self.buffer[n*w..n*w+w]
     }
 }
+
+
+// Straight alpha `top` over `bottom`, `top` covering `coverage` of the pixel.
+fn blend(bottom: Color, top: Color, coverage: f32) -> Color {
+    let a = top.a * coverage;
+    let out = a + bottom.a * (1.0 - a);
+    if out <= 0.0 { return Color::new(0.0, 0.0, 0.0, 0.0) }
+    let b = bottom.a * (1.0 - a);
+    Color::new((top.r * a + bottom.r * b) / out,
+               (top.g * a + bottom.g * b) / out,
+               (top.b * a + bottom.b * b) / out,
+               out)
+}
+
+
+// Coverage of a pixel by a shape, from the signed distance of its center
+// to the edge, negative inside.
+fn coverage(distance: f32) -> f32 {
+    (0.5 - distance).max(0.0).min(1.0)
+}
+
+
+// Distance from `p` to the segment `a`-`b`.
+fn segment_distance((px, py): (f32, f32), (ax, ay): (f32, f32), (bx, by): (f32, f32)) -> f32 {
+    let (dx, dy) = (bx - ax, by - ay);
+    let len = dx * dx + dy * dy;
+    let t = if len == 0.0 { 0.0 } else { (((px - ax) * dx + (py - ay) * dy) / len).max(0.0).min(1.0) };
+    let (x, y) = (ax + t * dx - px, ay + t * dy - py);
+    (x * x + y * y).sqrt()
+}
+
+
+// Approximate distance to the edge of an ellipse centered on the origin.
+fn ellipse_distance((x, y): (f32, f32), (rx, ry): (f32, f32)) -> f32 {
+    let f = (x / rx) * (x / rx) + (y / ry) * (y / ry) - 1.0;
+    let (gx, gy) = (2.0 * x / (rx * rx), 2.0 * y / (ry * ry));
+    let gradient = (gx * gx + gy * gy).sqrt();
+    if gradient == 0.0 { -rx.min(ry) } else { f / gradient }
+}
+
+
+fn inside_polygon((x, y): (f32, f32), points: &[(f32, f32)]) -> bool {
+    let mut inside = false;
+    let mut j = points.len() - 1;
+    for i in 0..points.len() {
+        let ((xi, yi), (xj, yj)) = (points[i], points[j]);
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+
+// Drawing, in pixels from the top left like `line_mut`, blended with straight alpha.
+// Shapes take `f32` coordinates and are antialiased, a pixel center is at `x + 0.5`.
+impl Canvas {
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height { return None }
+        Some ((self.height - y as usize - 1) * self.width + x as usize)
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<Color> {
+        self.index(x, y).map(|i| self.buffer[i])
+    }
+
+    // Replaces the pixel, without blending.
+    pub fn set(&mut self, x: i32, y: i32, color: Color) {
+        if let Some(i) = self.index(x, y) { self.buffer[i] = color }
+    }
+
+    pub fn blend(&mut self, x: i32, y: i32, color: Color, coverage: f32) {
+        if coverage <= 0.0 { return }
+        if let Some(i) = self.index(x, y) {
+            self.buffer[i] = blend(self.buffer[i], color, coverage);
+        }
+    }
+
+    // Blends `color` on the pixels of the box, by the coverage `f` gives their center.
+    fn shade<F>(&mut self, (left, top): (f32, f32), (right, bottom): (f32, f32), color: Color, f: F)
+            where F: Fn((f32, f32)) -> f32 {
+        let (left, top) = (left.floor().max(0.0) as i32, top.floor().max(0.0) as i32);
+        let right = right.ceil().min(self.width as f32) as i32;
+        let bottom = bottom.ceil().min(self.height as f32) as i32;
+        for y in top..bottom {
+            for x in left..right {
+                let c = f((x as f32 + 0.5, y as f32 + 0.5));
+                self.blend(x, y, color, c);
+            }
+        }
+    }
+
+    pub fn fill_rect(&mut self, (x, y): (i32, i32), (w, h): (i32, i32), color: Color) {
+        let (width, height) = (self.width as i32, self.height as i32);
+        for j in ::std::cmp::max(y, 0)..::std::cmp::min(y + h, height) {
+            for i in ::std::cmp::max(x, 0)..::std::cmp::min(x + w, width) {
+                self.blend(i, j, color, 1.0);
+            }
+        }
+    }
+
+    // Like `fill_rect`, replacing the pixels instead of blending.
+    pub fn set_rect(&mut self, (x, y): (i32, i32), (w, h): (i32, i32), color: Color) {
+        let (width, height) = (self.width as i32, self.height as i32);
+        for j in ::std::cmp::max(y, 0)..::std::cmp::min(y + h, height) {
+            for i in ::std::cmp::max(x, 0)..::std::cmp::min(x + w, width) {
+                self.set(i, j, color);
+            }
+        }
+    }
+
+    // A border `width` pixels wide inside the rectangle.
+    pub fn stroke_rect(&mut self, (x, y): (i32, i32), (w, h): (i32, i32), width: i32, color: Color) {
+        self.fill_rect((x, y), (w, width), color);
+        self.fill_rect((x, y + h - width), (w, width), color);
+        self.fill_rect((x, y + width), (width, h - 2 * width), color);
+        self.fill_rect((x + w - width, y + width), (width, h - 2 * width), color);
+    }
+
+    pub fn fill_rounded_rect(&mut self, (x, y): (f32, f32), (w, h): (f32, f32), radius: f32, color: Color) {
+        let radius = radius.min(w / 2.0).min(h / 2.0);
+        let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+        let (hw, hh) = (w / 2.0 - radius, h / 2.0 - radius);
+        self.shade((x, y), (x + w, y + h), color, |(px, py)| {
+            let dx = ((px - cx).abs() - hw).max(0.0);
+            let dy = ((py - cy).abs() - hh).max(0.0);
+            let outside = (dx * dx + dy * dy).sqrt() - radius;
+            let inside = ((px - cx).abs() - hw - radius).max((py - cy).abs() - hh - radius);
+            coverage(if dx > 0.0 || dy > 0.0 { outside } else { inside })
+        });
+    }
+
+    pub fn draw_line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: Color) {
+        let r = width / 2.0;
+        let top_left = (from.0.min(to.0) - r - 1.0, from.1.min(to.1) - r - 1.0);
+        let bottom_right = (from.0.max(to.0) + r + 1.0, from.1.max(to.1) + r + 1.0);
+        self.shade(top_left, bottom_right, color, |p| coverage(segment_distance(p, from, to) - r));
+    }
+
+    pub fn fill_ellipse(&mut self, (cx, cy): (f32, f32), (rx, ry): (f32, f32), color: Color) {
+        if rx <= 0.0 || ry <= 0.0 { return }
+        self.shade((cx - rx - 1.0, cy - ry - 1.0), (cx + rx + 1.0, cy + ry + 1.0), color,
+                   |(x, y)| coverage(ellipse_distance((x - cx, y - cy), (rx, ry))));
+    }
+
+    // The outline `width` pixels wide, centered on the edge.
+    pub fn stroke_ellipse(&mut self, (cx, cy): (f32, f32), (rx, ry): (f32, f32), width: f32, color: Color) {
+        if rx <= 0.0 || ry <= 0.0 { return }
+        let r = width / 2.0;
+        self.shade((cx - rx - r - 1.0, cy - ry - r - 1.0), (cx + rx + r + 1.0, cy + ry + r + 1.0), color,
+                   |(x, y)| coverage(ellipse_distance((x - cx, y - cy), (rx, ry)).abs() - r));
+    }
+
+    pub fn fill_circle(&mut self, center: (f32, f32), radius: f32, color: Color) {
+        self.fill_ellipse(center, (radius, radius), color);
+    }
+
+    pub fn stroke_circle(&mut self, center: (f32, f32), radius: f32, width: f32, color: Color) {
+        self.stroke_ellipse(center, (radius, radius), width, color);
+    }
+
+    // Even-odd filling, antialiased by sampling each pixel 4 by 4 times.
+    pub fn fill_polygon(&mut self, points: &[(f32, f32)], color: Color) {
+        const SAMPLES: usize = 4;
+
+        if points.len() < 3 { return }
+        let left = points.iter().fold(::std::f32::MAX, |a, p| a.min(p.0));
+        let top = points.iter().fold(::std::f32::MAX, |a, p| a.min(p.1));
+        let right = points.iter().fold(::std::f32::MIN, |a, p| a.max(p.0));
+        let bottom = points.iter().fold(::std::f32::MIN, |a, p| a.max(p.1));
+        self.shade((left, top), (right, bottom), color, |(x, y)| {
+            let mut hits = 0;
+            for j in 0..SAMPLES {
+                for i in 0..SAMPLES {
+                    let sx = x - 0.5 + (i as f32 + 0.5) / SAMPLES as f32;
+                    let sy = y - 0.5 + (j as f32 + 0.5) / SAMPLES as f32;
+                    if inside_polygon((sx, sy), points) { hits += 1 }
+                }
+            }
+            hits as f32 / (SAMPLES * SAMPLES) as f32
+        });
+    }
+
+    pub fn stroke_polygon(&mut self, points: &[(f32, f32)], width: f32, color: Color) {
+        for i in 0..points.len() {
+            self.draw_line(points[i], points[(i + 1) % points.len()], width, color);
+        }
+    }
+
+    // Replaces the pixels of the color at `(x, y)` connected to it.
+    pub fn flood_fill(&mut self, x: i32, y: i32, color: Color) {
+        let target = match self.get(x, y) {
+            Some (x) => x,
+            None => return,
+        };
+        if target == color { return }
+        let mut stack = vec![(x, y)];
+        while let Some((x, y)) = stack.pop() {
+            if self.get(x, y) != Some (target) { continue }
+            self.set(x, y, color);
+            stack.push((x + 1, y));
+            stack.push((x - 1, y));
+            stack.push((x, y + 1));
+            stack.push((x, y - 1));
+        }
+    }
+
+    // `other` blended over this canvas, its top left at `(x, y)`.
+    pub fn blit(&mut self, other: &Canvas, (x, y): (i32, i32)) {
+        for j in 0..other.height as i32 {
+            for i in 0..other.width as i32 {
+                let color = other.get(i, j).unwrap();
+                self.blend(x + i, y + j, color, 1.0);
+            }
+        }
+    }
+
+    pub fn blit_image(&mut self, image: &image::RgbaImage, (x, y): (i32, i32)) {
+        for (i, j, pixel) in image.enumerate_pixels() {
+            self.blend(x + i as i32, y + j as i32, from_rgba(pixel), 1.0);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use image;
+    use color::Color;
+    use super::Canvas;
+
+    // Drawn by `rects`, regenerate with `save_png` after a deliberate change.
+    const RECTS: &'static [u8] = include_bytes!("golden/rects.png");
+
+    #[test]
+    fn image_round_trip() {
+        let mut canvas = Canvas::new(5, 3);
+        for y in 0..3 {
+            for x in 0..5 {
+                let k = (x * 51 + y * 17) as f32;
+                canvas.set(x, y, Color::new(k / 255.0, (255.0 - k) / 255.0, 1.0, (y * 120) as f32 / 255.0));
+            }
+        }
+        let image = image::DynamicImage::ImageRgba8(canvas.to_image());
+        let back = Canvas::from_image(&image);
+        assert_eq!((back.width, back.height), (5, 3));
+        assert!(back.buffer() == canvas.buffer());
+        // The top left pixel, where the canvas starts with its bottom row.
+        assert_eq!(back.get(0, 0), Some (Color::new(0.0, 1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn rects() {
+        let mut canvas = Canvas::with_color(8, 6, Color::white());
+        canvas.stroke_rect((0, 0), (8, 6), 1, Color::black());
+        canvas.fill_rect((1, 1), (3, 2), Color::new(1.0, 0.0, 0.0, 1.0));
+        // Half blue over white.
+        canvas.fill_rect((4, 2), (3, 3), Color::new(0.0, 0.0, 1.0, 0.5));
+        // Clipped to the two top right pixels.
+        canvas.fill_rect((6, -2), (4, 3), Color::new(0.0, 1.0, 0.0, 1.0));
+        let golden = image::load_from_memory(RECTS).unwrap().to_rgba();
+        assert_eq!(canvas.to_image().dimensions(), golden.dimensions());
+        assert!(canvas.to_image().into_raw() == golden.into_raw());
+    }
+
+    #[test]
+    fn set_rect_replaces() {
+        let half = Color::new(0.0, 0.0, 0.0, 0.5);
+        let mut canvas = Canvas::with_color(4, 4, half);
+        canvas.set_rect((2, 2), (4, 4), half);
+        canvas.fill_rect((0, 0), (2, 2), half);
+        assert_eq!(canvas.get(3, 3), Some (half));
+        assert_eq!(canvas.get(0, 0), Some (Color::new(0.0, 0.0, 0.0, 0.75)));
+    }
+
+    #[test]
+    fn empty_ellipses() {
+        let mut canvas = Canvas::with_color(4, 4, Color::white());
+        canvas.fill_ellipse((2.0, 2.0), (0.0, 2.0), Color::black());
+        canvas.stroke_ellipse((2.0, 2.0), (2.0, -1.0), 1.0, Color::black());
+        canvas.fill_circle((2.0, 2.0), 0.0, Color::black());
+        assert!(canvas.buffer().iter().all(|x| *x == Color::white()));
+    }
+
+    fn alpha(canvas: &Canvas, x: i32, y: i32) -> f32 {
+        canvas.get(x, y).unwrap().a
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn lines() {
+        // Black on a transparent canvas, the alpha is the coverage.
+        let mut canvas = Canvas::new(8, 5);
+        canvas.draw_line((1.0, 2.5), (7.0, 2.5), 1.0, Color::black());
+        assert!(close(alpha(&canvas, 3, 2), 1.0));
+        // The round caps cover half of the end pixels.
+        assert!(close(alpha(&canvas, 0, 2), 0.5));
+        assert!(close(alpha(&canvas, 7, 2), 0.5));
+        assert_eq!(alpha(&canvas, 3, 1), 0.0);
+        assert_eq!(alpha(&canvas, 3, 3), 0.0);
+
+        let mut canvas = Canvas::new(5, 5);
+        canvas.draw_line((0.0, 0.0), (5.0, 5.0), 1.0, Color::black());
+        assert!(close(alpha(&canvas, 2, 2), 1.0));
+        // Their centers are sqrt(0.5) from the middle of the line, 0.5 wide each way.
+        let edge = 0.5 - (0.5f32.sqrt() - 0.5);
+        assert!(close(alpha(&canvas, 3, 2), edge));
+        assert!(close(alpha(&canvas, 2, 3), edge));
+        assert_eq!(alpha(&canvas, 4, 0), 0.0);
+    }
+
+    #[test]
+    fn ellipses() {
+        let mut canvas = Canvas::new(8, 8);
+        canvas.fill_ellipse((4.0, 4.0), (3.0, 2.0), Color::black());
+        assert!(close(alpha(&canvas, 3, 3), 1.0));
+        assert_eq!(alpha(&canvas, 0, 0), 0.0);
+        assert_eq!(alpha(&canvas, 0, 3), 0.0);
+        let edge = alpha(&canvas, 1, 3);
+        assert!(edge > 0.5 && edge < 1.0, "{}", edge);
+        // Symmetric around the center.
+        for &(x, y) in [(6, 3), (1, 4), (6, 4)].iter() {
+            assert!(close(alpha(&canvas, x, y), edge));
+        }
+
+        let mut canvas = Canvas::new(8, 8);
+        canvas.stroke_circle((4.0, 4.0), 3.0, 1.0, Color::black());
+        assert_eq!(alpha(&canvas, 3, 3), 0.0);
+        assert!(alpha(&canvas, 1, 3) > 0.5);
+        assert!(alpha(&canvas, 4, 1) > 0.5);
+    }
+
+    #[test]
+    fn polygons() {
+        let mut canvas = Canvas::new(6, 6);
+        canvas.fill_polygon(&[(1.0, 1.0), (5.0, 1.0), (5.0, 5.0), (1.0, 5.0)], Color::black());
+        assert_eq!(alpha(&canvas, 2, 2), 1.0);
+        assert_eq!(alpha(&canvas, 4, 4), 1.0);
+        assert_eq!(alpha(&canvas, 0, 0), 0.0);
+        assert_eq!(alpha(&canvas, 5, 2), 0.0);
+
+        // Edges through the middle of pixels cover half of them.
+        let mut canvas = Canvas::new(6, 6);
+        canvas.fill_polygon(&[(1.5, 1.0), (3.5, 1.0), (3.5, 3.0), (1.5, 3.0)], Color::black());
+        assert_eq!(alpha(&canvas, 1, 1), 0.5);
+        assert_eq!(alpha(&canvas, 2, 2), 1.0);
+        assert_eq!(alpha(&canvas, 3, 2), 0.5);
+        assert_eq!(alpha(&canvas, 2, 3), 0.0);
+    }
+
+    #[test]
+    fn flood_fill() {
+        let (white, red, blue) = (Color::white(), Color::new(1.0, 0.0, 0.0, 1.0), Color::new(0.0, 0.0, 1.0, 1.0));
+        let mut canvas = Canvas::with_color(5, 5, white);
+        canvas.stroke_rect((1, 1), (3, 3), 1, Color::black());
+        canvas.flood_fill(0, 0, red);
+        // Around the border, not through it.
+        assert_eq!(canvas.get(4, 4), Some (red));
+        assert_eq!(canvas.get(0, 2), Some (red));
+        assert_eq!(canvas.get(1, 1), Some (Color::black()));
+        assert_eq!(canvas.get(2, 2), Some (white));
+        canvas.flood_fill(2, 2, blue);
+        assert_eq!(canvas.get(2, 2), Some (blue));
+        assert_eq!(canvas.get(0, 0), Some (red));
+        canvas.flood_fill(-1, 0, blue);
+        assert_eq!(canvas.get(0, 0), Some (red));
+    }
+
+    #[test]
+    fn blit() {
+        let red = Color::new(1.0, 0.0, 0.0, 1.0);
+        let mut other = Canvas::new(2, 2);
+        other.set(0, 0, red);
+        other.set(1, 1, Color::new(0.0, 0.0, 0.0, 0.5));
+        let mut canvas = Canvas::with_color(4, 4, Color::white());
+        // Clipped on every side.
+        canvas.blit(&other, (3, 3));
+        canvas.blit(&other, (-1, -1));
+        assert_eq!(canvas.get(3, 3), Some (red));
+        assert_eq!(canvas.get(0, 0), Some (Color::new(0.5, 0.5, 0.5, 1.0)));
+        // Transparent pixels leave it as it was.
+        assert_eq!(canvas.get(1, 0), Some (Color::white()));
+        assert_eq!(canvas.get(0, 1), Some (Color::white()));
+        assert_eq!(canvas.buffer().iter().filter(|x| **x != Color::white()).count(), 2);
+    }
+
+    #[test]
+    fn blit_image() {
+        let image = image::ImageBuffer::from_fn(2, 2, |x, y| {
+            image::Rgba([if x == 0 && y == 0 { 255 } else { 0 }, 0, 0, if y == 0 { 255 } else { 0 }])
+        });
+        let mut canvas = Canvas::with_color(3, 3, Color::white());
+        canvas.blit_image(&image, (1, 2));
+        // Top row of the image first, only that row is opaque, and it is cut at the bottom.
+        assert_eq!(canvas.get(1, 2), Some (Color::new(1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(canvas.get(2, 2), Some (Color::black()));
+        assert_eq!(canvas.get(1, 1), Some (Color::white()));
+    }
+}
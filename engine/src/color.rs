@@ -1,10 +1,10 @@
 use glium::texture::{PixelValue, ClientFormat};
 use glium::uniforms::{AsUniformValue, UniformValue};
-use std::ops::Add;
+use std::ops::{Add, Mul};
 use std::cmp::{PartialEq, Eq};
 
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct Color {
     pub r: f32, pub g: f32, pub b: f32, pub a: f32
 }
@@ -70,6 +70,16 @@ impl Add for Color {
 }
 
 
+// Component by component, a tint over another.
+impl Mul for Color {
+    type Output = Color;
+
+    fn mul(self, rhs: Color) -> Color {
+        Color::new(self.r * rhs.r, self.g * rhs.g, self.b * rhs.b, self.a * rhs.a)
+    }
+}
+
+
 impl PartialEq<Color> for Color {
     fn eq(&self, other: &Color) -> bool {
         self.r == other.r && self.g == other.g &&
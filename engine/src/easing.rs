@@ -0,0 +1,287 @@
+use std::f32::consts::PI;
+use std::default::Default;
+use std::str::FromStr;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    In,
+    Out,
+    InOut,
+}
+
+
+// Robert Penner's easing equations, plus CSS-like cubic Bézier timing.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Easing {
+    Linear,
+    Quad(Mode),
+    Cubic(Mode),
+    Quart(Mode),
+    Expo(Mode),
+    Sine(Mode),
+    Back(Mode),
+    Elastic(Mode),
+    Bounce(Mode),
+    // Control points (x1, y1) and (x2, y2), the curve starts at (0, 0) and ends at (1, 1).
+    Bezier(f32, f32, f32, f32),
+}
+
+
+impl Default for Easing {
+    fn default() -> Easing {
+        Easing::Linear
+    }
+}
+
+
+fn quad(t: f32) -> f32 { t * t }
+
+fn cubic(t: f32) -> f32 { t * t * t }
+
+fn quart(t: f32) -> f32 { t * t * t * t }
+
+fn expo(t: f32) -> f32 {
+    if t == 0.0 { 0.0 } else { (2.0f32).powf(10.0 * (t - 1.0)) }
+}
+
+fn sine(t: f32) -> f32 { 1.0 - (t * PI / 2.0).cos() }
+
+fn back(t: f32) -> f32 {
+    const S: f32 = 1.70158;
+    t * t * ((S + 1.0) * t - S)
+}
+
+fn elastic(t: f32) -> f32 {
+    if t == 0.0 || t == 1.0 { return t }
+    const P: f32 = 0.3;
+    let t = t - 1.0;
+    -(2.0f32).powf(10.0 * t) * ((t - P / 4.0) * (2.0 * PI) / P).sin()
+}
+
+fn bounce(t: f32) -> f32 {
+    1.0 - bounce_out(1.0 - t)
+}
+
+fn bounce_out(t: f32) -> f32 {
+    if t < 1.0 / 2.75 {
+        7.5625 * t * t
+    }
+    else if t < 2.0 / 2.75 {
+        let t = t - 1.5 / 2.75;
+        7.5625 * t * t + 0.75
+    }
+    else if t < 2.5 / 2.75 {
+        let t = t - 2.25 / 2.75;
+        7.5625 * t * t + 0.9375
+    }
+    else {
+        let t = t - 2.625 / 2.75;
+        7.5625 * t * t + 0.984375
+    }
+}
+
+
+// Every ease-in curve gives its ease-out and ease-in-out forms.
+fn with_mode<F: Fn(f32) -> f32>(mode: Mode, f: F, t: f32) -> f32 {
+    match mode {
+        Mode::In => f(t),
+        Mode::Out => 1.0 - f(1.0 - t),
+        Mode::InOut => {
+            if t < 0.5 { f(t * 2.0) / 2.0 }
+            else { 1.0 - f((1.0 - t) * 2.0) / 2.0 }
+        }
+    }
+}
+
+
+fn bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+    // Coordinates of the curve on one axis, and its derivative.
+    fn at(a: f32, b: f32, t: f32) -> f32 {
+        let r = 1.0 - t;
+        3.0 * r * r * t * a + 3.0 * r * t * t * b + t * t * t
+    }
+    fn slope(a: f32, b: f32, t: f32) -> f32 {
+        let r = 1.0 - t;
+        3.0 * r * r * a + 6.0 * r * t * (b - a) + 3.0 * t * t * (1.0 - b)
+    }
+
+    // Find the curve parameter for `x`, Newton's method first, bisection if it fails.
+    let mut t = x;
+    for _ in 0..8 {
+        let error = at(x1, x2, t) - x;
+        if error.abs() < 1e-6 { return at(y1, y2, t) }
+        let d = slope(x1, x2, t);
+        if d.abs() < 1e-6 { break }
+        t = t - error / d;
+    }
+    let (mut low, mut high) = (0.0, 1.0);
+    t = x;
+    for _ in 0..32 {
+        let value = at(x1, x2, t);
+        if (value - x).abs() < 1e-6 { break }
+        if value < x { low = t } else { high = t }
+        t = (low + high) / 2.0;
+    }
+    at(y1, y2, t)
+}
+
+
+impl Easing {
+    // `t` is clamped to [0, 1], the result may overshoot for `Back` and `Elastic`.
+    pub fn apply(&self, t: f32) -> f32 {
+        use self::Easing::*;
+
+        let t = t.max(0.0).min(1.0);
+        match *self {
+            Linear => t,
+            Quad(mode) => with_mode(mode, quad, t),
+            Cubic(mode) => with_mode(mode, cubic, t),
+            Quart(mode) => with_mode(mode, quart, t),
+            Expo(mode) => with_mode(mode, expo, t),
+            Sine(mode) => with_mode(mode, sine, t),
+            Back(mode) => with_mode(mode, back, t),
+            Elastic(mode) => with_mode(mode, elastic, t),
+            Bounce(mode) => with_mode(mode, bounce, t),
+            Bezier(x1, y1, x2, y2) => {
+                if t == 0.0 || t == 1.0 { t } else { bezier(x1, y1, x2, y2, t) }
+            }
+        }
+    }
+
+    // The curve played backwards in time, `1 - apply(1 - t)`.
+    pub fn mirrored(&self) -> Easing {
+        use self::Easing::*;
+
+        let flip = |mode| match mode {
+            Mode::In => Mode::Out,
+            Mode::Out => Mode::In,
+            Mode::InOut => Mode::InOut,
+        };
+        match *self {
+            Linear => Linear,
+            Quad(mode) => Quad(flip(mode)),
+            Cubic(mode) => Cubic(flip(mode)),
+            Quart(mode) => Quart(flip(mode)),
+            Expo(mode) => Expo(flip(mode)),
+            Sine(mode) => Sine(flip(mode)),
+            Back(mode) => Back(flip(mode)),
+            Elastic(mode) => Elastic(flip(mode)),
+            Bounce(mode) => Bounce(flip(mode)),
+            Bezier(x1, y1, x2, y2) => Bezier(1.0 - x2, 1.0 - y2, 1.0 - x1, 1.0 - y1),
+        }
+    }
+}
+
+
+impl FromStr for Easing {
+    type Err = String;
+
+    // `linear`, `<curve>-in`, `<curve>-out`, `<curve>-in-out` or `bezier:x1,y1,x2,y2`.
+    fn from_str(s: &str) -> Result<Easing, String> {
+        use self::Easing::*;
+
+        if s == "linear" { return Ok (Linear) }
+        if s.starts_with("bezier:") {
+            let points: Vec<f32> = s["bezier:".len()..].split(',')
+                .filter_map(|x| x.trim().parse().ok())
+                .collect();
+            if points.len() != 4 {
+                return Err (format!("bezier needs 4 numbers: {}", s));
+            }
+            return Ok (Bezier(points[0], points[1], points[2], points[3]));
+        }
+        let (name, mode) = if s.ends_with("-in-out") {
+            (&s[..s.len()-7], Mode::InOut)
+        } else if s.ends_with("-out") {
+            (&s[..s.len()-4], Mode::Out)
+        } else if s.ends_with("-in") {
+            (&s[..s.len()-3], Mode::In)
+        } else {
+            return Err (format!("unknown easing: {}", s))
+        };
+        Ok (match name {
+            "quad" => Quad(mode),
+            "cubic" => Cubic(mode),
+            "quart" => Quart(mode),
+            "expo" => Expo(mode),
+            "sine" => Sine(mode),
+            "back" => Back(mode),
+            "elastic" => Elastic(mode),
+            "bounce" => Bounce(mode),
+            _ => return Err (format!("unknown easing: {}", s)),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Easing, Mode};
+    use super::Easing::*;
+
+    const MODES: [Mode; 3] = [Mode::In, Mode::Out, Mode::InOut];
+
+    fn curves(mode: Mode) -> Vec<Easing> {
+        vec![Quad(mode), Cubic(mode), Quart(mode), Expo(mode), Sine(mode),
+             Back(mode), Elastic(mode), Bounce(mode)]
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn endpoints() {
+        let mut all = vec![Linear, Bezier(0.25, 0.1, 0.25, 1.0), Bezier(0.68, -0.55, 0.27, 1.55)];
+        for &mode in MODES.iter() {
+            all.extend(curves(mode));
+        }
+        for easing in all {
+            assert!(close(easing.apply(0.0), 0.0), "{:?} at 0: {}", easing, easing.apply(0.0));
+            assert!(close(easing.apply(1.0), 1.0), "{:?} at 1: {}", easing, easing.apply(1.0));
+        }
+    }
+
+    #[test]
+    fn monotonic() {
+        for &mode in MODES.iter() {
+            for easing in vec![Quad(mode), Cubic(mode), Quart(mode), Expo(mode), Sine(mode)] {
+                let mut last = easing.apply(0.0);
+                for i in 1..101 {
+                    let value = easing.apply(i as f32 / 100.0);
+                    assert!(value >= last - 1e-6, "{:?} decreases at {}", easing, i);
+                    last = value;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mirrored() {
+        let mut all = vec![Linear, Bezier(0.42, 0.0, 1.0, 1.0), Bezier(0.1, 0.6, 0.7, 0.2)];
+        for &mode in MODES.iter() {
+            all.extend(curves(mode));
+        }
+        for easing in all {
+            let mirror = easing.mirrored();
+            for i in 0..11 {
+                let t = i as f32 / 10.0;
+                let (a, b) = (mirror.apply(t), 1.0 - easing.apply(1.0 - t));
+                assert!((a - b).abs() < 1e-3, "{:?} at {}: {} and {}", easing, t, a, b);
+            }
+            assert_eq!(mirror.mirrored(), easing);
+        }
+    }
+
+    #[test]
+    fn bezier_monotonic() {
+        let easing = Bezier(0.42, 0.0, 0.58, 1.0);
+        let mut last = 0.0;
+        for i in 1..101 {
+            let value = easing.apply(i as f32 / 100.0);
+            assert!(value >= last - 1e-6, "decreases at {}", i);
+            last = value;
+        }
+    }
+}
@@ -1,21 +1,43 @@
+use std::rc::Rc;
+use std::cell::RefCell;
 use glium::Display;
-use widget::Label;
-use timer::ProgramTimer;
+use widget::{Label, Overlay};
+use timer::{ProgramTimer, Clock};
 use text::{TextStyle, GlyphCache};
+use scheduler::Scheduler;
+use resources::Loader;
+use atlas::Atlas;
+
+const UPLOADS_PER_FRAME: usize = 2;
+const ATLAS_SIZE: usize = 1024;
+
 
 pub struct Engine<'display> {
     pub timer: ProgramTimer,
     pub display: &'display Display,
     pub glyph_cache: GlyphCache,
+    // Updated by the caller along with the other `Update`s, on the clock of its choice.
+    pub scheduler: Scheduler,
+    // Give it to the managers loading in the background, see `Manager::loader`.
+    pub loader: Loader,
+    // Shared by the widgets built with `WidgetBuilder::build_in`.
+    pub atlas: Rc<RefCell<Atlas>>,
 }
 
 
 impl<'display> Engine<'display> {
     pub fn new(display: &'display Display) -> Engine<'display> {
+        Engine::with_clock(display, box ::timer::RealClock)
+    }
+
+    pub fn with_clock(display: &'display Display, clock: Box<Clock>) -> Engine<'display> {
         Engine {
-            timer: ProgramTimer::new(),
+            timer: ProgramTimer::with_clock(clock),
             display: display,
             glyph_cache: GlyphCache::new(),
+            scheduler: Scheduler::new(),
+            loader: Loader::new(4),
+            atlas: Rc::new(RefCell::new(Atlas::new(ATLAS_SIZE))),
         }
     }
 
@@ -24,7 +46,14 @@ impl<'display> Engine<'display> {
         Label::new(self.glyph_cache.clone(), style, x)
     }
 
+    pub fn overlay(&self, style: TextStyle) -> Overlay {
+        let style = style.factor(self.display.get_window().unwrap().hidpi_factor());
+        Overlay::new(self.glyph_cache.clone(), style)
+    }
+
     pub fn update(&mut self) {
+        ::profiler::with(|x| x.end_frame());
+        self.loader.upload(self.display, UPLOADS_PER_FRAME);
         self.timer.update();
     }
 }
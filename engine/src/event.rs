@@ -33,6 +33,22 @@ impl EventStream {
         }).map(|e| Event::Window(e)).collect();
         EventStream(events)
     }
+
+    // Without window events, for updates run several times per frame.
+    pub fn empty() -> EventStream {
+        EventStream(Vec::new())
+    }
+
+    pub fn append(&mut self, other: EventStream) {
+        let &mut EventStream (ref mut x) = self;
+        let EventStream (other) = other;
+        x.extend(other);
+    }
+
+    pub fn push(&mut self, event: Event) {
+        let &mut EventStream (ref mut x) = self;
+        x.push(event);
+    }
 }
 
 
@@ -2,7 +2,7 @@ use uuid::Uuid;
 
 
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
 pub struct Id(Uuid);
 
 
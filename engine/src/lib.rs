@@ -8,6 +8,7 @@ extern crate time;
 extern crate uuid;
 extern crate freetype;
 extern crate threadpool;
+extern crate rustc_serialize;
 extern crate unicode_normalization;
 
 
@@ -26,8 +27,10 @@ macro_rules! na {
 
 #[macro_use]
 pub mod animation;
+pub mod atlas;
 pub mod canvas;
 pub mod color;
+pub mod easing;
 pub mod event;
 pub mod math;
 pub mod resources;
@@ -35,7 +38,11 @@ pub mod sprite;
 pub mod timer;
 pub mod widget;
 pub mod text;
+pub mod timeline;
 pub mod id;
+pub mod scheduler;
+pub mod profiler;
+pub mod vfs;
 mod transform;
 mod mesh;
 mod camera;
@@ -46,16 +53,22 @@ mod engine;
 
 pub use nalgebra as na;
 pub use glium::{Frame, Display};
-pub use timer::{ProgramTimer, Timer, Ms};
+pub use timer::{ProgramTimer, Timer, Ms, Clock, RealClock, ManualClock};
 pub use render::{Renderable, Renderer};
 pub use sprite::Sprite;
+pub use sprite::tilemap::TileMap;
+pub use sprite::clip::{Clip, Clips};
+pub use sprite::sheet::Sheet;
 pub use event::{Event, WindowEvent, EventStream, Update};
-pub use widget::{Label, Widget, WidgetBuilder};
-pub use resources::Manager;
+pub use widget::{Label, Overlay, Widget, WidgetBuilder};
+pub use resources::{Manager, Handle, Live, Loader};
 pub use transform::Transform;
-pub use texture::Texture;
+pub use texture::{Texture, TextureOptions, Filter, Wrap};
+pub use atlas::Atlas;
 pub use engine::Engine;
+pub use scheduler::Scheduler;
 pub use camera::Camera;
+pub use easing::Easing;
 
 use glium::glutin::WindowBuilder;
 use glium::DisplayBuild;
@@ -7,9 +7,10 @@ use na::Vec2;
 pub struct Vertex {
     pub position: Vec2<f32>,
     pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
 }
 
-implement_vertex!(Vertex, position, tex_coords);
+implement_vertex!(Vertex, position, tex_coords, color);
 
 
 pub type VertexBuffer = glium::VertexBuffer<Vertex>;
@@ -0,0 +1,198 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use timer::{Clock, RealClock, Ns};
+
+const MS_NS: f32 = 1_000_000.0;
+const HISTORY: usize = 120;
+
+// Time of a whole frame, from one `end_frame` to the next.
+pub const FRAME: &'static str = "frame";
+
+
+thread_local!(static PROFILER: RefCell<Profiler> = RefCell::new(Profiler::new()));
+
+
+// The profiler of this thread, engine code records into it through `scope` and `count`.
+pub fn with<F, R>(f: F) -> R where F: FnOnce(&mut Profiler) -> R {
+    PROFILER.with(|x| f(&mut *x.borrow_mut()))
+}
+
+
+// Adds the time until it is dropped to the scope `name`.
+pub fn scope(name: &'static str) -> Scope {
+    Scope { name: name, start: with(|x| x.now()) }
+}
+
+
+pub fn count(name: &'static str, n: usize) {
+    with(|x| x.count(name, n))
+}
+
+
+pub struct Scope {
+    name: &'static str,
+    start: Ns,
+}
+
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let (name, start) = (self.name, self.start);
+        with(|x| {
+            let elapsed = x.now() - start;
+            x.add(name, elapsed);
+        });
+    }
+}
+
+
+// In milliseconds, over the recorded frames.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Stats {
+    pub last: f32,
+    pub min: f32,
+    pub avg: f32,
+    pub p99: f32,
+}
+
+
+struct Record {
+    current: Ns,
+    history: VecDeque<Ns>,
+}
+
+
+pub struct Profiler {
+    clock: Box<Clock>,
+    frame: Ns,
+    scopes: BTreeMap<&'static str, Record>,
+    // Counted during the current frame, and during the last one.
+    counters: BTreeMap<&'static str, (usize, usize)>,
+}
+
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        let clock = box RealClock;
+        Profiler {
+            frame: clock.now_ns(),
+            clock: clock,
+            scopes: BTreeMap::new(),
+            counters: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: Box<Clock>) {
+        self.frame = clock.now_ns();
+        self.clock = clock;
+    }
+
+    fn now(&self) -> Ns {
+        self.clock.now_ns()
+    }
+
+    pub fn add(&mut self, name: &'static str, ns: Ns) {
+        let record = self.scopes.entry(name).or_insert_with(|| Record {
+            current: 0,
+            history: VecDeque::with_capacity(HISTORY),
+        });
+        record.current += ns;
+    }
+
+    pub fn count(&mut self, name: &'static str, n: usize) {
+        self.counters.entry(name).or_insert((0, 0)).0 += n;
+    }
+
+    // Closes the frame: what every scope took during it goes to its history.
+    pub fn end_frame(&mut self) {
+        let now = self.now();
+        let frame = now - self.frame;
+        self.frame = now;
+        self.add(FRAME, frame);
+        for (_, record) in self.scopes.iter_mut() {
+            if record.history.len() == HISTORY {
+                record.history.pop_front();
+            }
+            record.history.push_back(record.current);
+            record.current = 0;
+        }
+        for (_, counter) in self.counters.iter_mut() {
+            *counter = (0, counter.0);
+        }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.scopes.keys().cloned().collect()
+    }
+
+    // Oldest first, in milliseconds.
+    pub fn history(&self, name: &str) -> Vec<f32> {
+        self.scopes.get(name).map_or(Vec::new(), |x| {
+            x.history.iter().map(|&ns| ns as f32 / MS_NS).collect()
+        })
+    }
+
+    pub fn stats(&self, name: &str) -> Option<Stats> {
+        let mut samples = self.history(name);
+        let last = match samples.last() {
+            Some (&x) => x,
+            None => return None,
+        };
+        let avg = samples.iter().fold(0.0, |a, b| a + b) / samples.len() as f32;
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let p99 = ((samples.len() - 1) as f32 * 0.99).round() as usize;
+        Some (Stats { last: last, min: samples[0], avg: avg, p99: samples[p99] })
+    }
+
+    // As counted during the last frame.
+    pub fn counter(&self, name: &str) -> usize {
+        self.counters.get(name).map_or(0, |x| x.1)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use timer::ManualClock;
+    use super::{Profiler, Stats, FRAME, HISTORY};
+
+    fn profiler() -> (ManualClock, Profiler) {
+        let clock = ManualClock::new();
+        let mut profiler = Profiler::new();
+        profiler.set_clock(box clock.clone());
+        (clock, profiler)
+    }
+
+    #[test]
+    fn stats() {
+        let (clock, mut profiler) = profiler();
+        assert_eq!(profiler.stats(FRAME), None);
+        // Slow frames out of the history by the end.
+        for _ in 0..10 {
+            clock.advance_ms(500);
+            profiler.end_frame();
+        }
+        for ms in 1..HISTORY as u64 + 1 {
+            clock.advance_ms(ms);
+            profiler.end_frame();
+        }
+        assert_eq!(profiler.history(FRAME).len(), HISTORY);
+        assert_eq!(profiler.stats(FRAME), Some (Stats { last: 120.0, min: 1.0, avg: 60.5, p99: 119.0 }));
+    }
+
+    #[test]
+    fn counters() {
+        let (_, mut profiler) = profiler();
+        profiler.count("draws", 3);
+        profiler.count("draws", 2);
+        assert_eq!(profiler.counter("draws"), 0);
+        profiler.end_frame();
+        assert_eq!(profiler.counter("draws"), 5);
+        profiler.count("draws", 1);
+        assert_eq!(profiler.counter("draws"), 5);
+        profiler.end_frame();
+        assert_eq!(profiler.counter("draws"), 1);
+        profiler.end_frame();
+        assert_eq!(profiler.counter("draws"), 0);
+    }
+}
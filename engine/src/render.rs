@@ -1,7 +1,12 @@
 use glium::{Display, Program, DrawParameters, Frame, Surface};
 use glium::uniforms::Uniforms;
 use mesh::Mesh;
+use profiler;
 pub use math::Mat;
+use std::io::Read;
+
+const DEFAULT_VERTEX: &'static str = include_str!("shader/140/default.vert");
+const DEFAULT_FRAGMENT: &'static str = include_str!("shader/140/default.frag");
 
 
 pub trait Renderable {
@@ -28,29 +33,72 @@ pub struct Renderer<'display> {
 
 impl<'display> Renderer<'display> {
     pub fn new(display: &'display Display) -> Renderer<'display> {
-        Renderer::with_shader(display,
-                              include_str!("shader/140/default.vert"),
-                              include_str!("shader/140/default.frag"))
+        Renderer::try_with_shader(display, DEFAULT_VERTEX, DEFAULT_FRAGMENT).unwrap()
     }
 
+    // Falls back on the built-in shader if this one does not compile.
     pub fn with_shader(display: &'display Display, vertex: &str, fragment: &str)
         -> Renderer<'display>
     {
-        let program = program!(display,
+        use std::io::Write;
+
+        match Renderer::try_with_shader(display, vertex, fragment) {
+            Ok (x) => x,
+            Err (e) => {
+                let _ = writeln!(::std::io::stderr(), "{}, using the built-in shader", e);
+                Renderer::new(display)
+            }
+        }
+    }
+
+    // Sources read from anywhere, files, archives or embedded bytes.
+    pub fn from_reader<R: Read>(display: &'display Display, mut vertex: R, mut fragment: R)
+        -> Renderer<'display>
+    {
+        use std::io::Write;
+
+        let (mut v, mut f) = (String::new(), String::new());
+        match (vertex.read_to_string(&mut v), fragment.read_to_string(&mut f)) {
+            (Ok (_), Ok (_)) => Renderer::with_shader(display, &v, &f),
+            (Err (e), _) | (_, Err (e)) => {
+                let _ = writeln!(::std::io::stderr(),
+                                 "cannot read the shader: {}, using the built-in shader", e);
+                Renderer::new(display)
+            }
+        }
+    }
+
+    pub fn try_with_shader(display: &'display Display, vertex: &str, fragment: &str)
+        -> Result<Renderer<'display>, String>
+    {
+        let program = match program!(display,
             140 => {
                 vertex: vertex,
                 fragment: fragment,
             },
-        ).unwrap();
-        Renderer {
+        ) {
+            Ok (x) => x,
+            Err (e) => return Err (format!("cannot build the shader: {:?}", e)),
+        };
+        Ok (Renderer {
             display: display,
             program: program,
             params: Renderer::build_params(),
-        }
+        })
+    }
+
+    // A new shader in place, for sources watched through a `Manager<String>`.
+    // The current one is kept if this one does not compile.
+    pub fn set_shader(&mut self, vertex: &str, fragment: &str) -> Result<(), String> {
+        let renderer = try!(Renderer::try_with_shader(self.display, vertex, fragment));
+        self.program = renderer.program;
+        Ok (())
     }
 
     pub fn draw<U>(&self, target: &mut Frame, mesh: &Mesh, uniforms: &U)
             where U: Uniforms {
+        profiler::count("draw calls", 1);
+        profiler::count("sprites", mesh.vertex_buffer.len() / 4);
         target.draw(
             &mesh.vertex_buffer,
             &mesh.index_buffer,
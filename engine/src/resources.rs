@@ -1,44 +1,411 @@
+use std::fmt;
+use std::io;
+use std::time::SystemTime;
 use std::path::{Path, PathBuf};
 use std::rc::{Rc, Weak};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::sync::mpsc::{channel, Receiver};
 use std::convert::AsRef;
 use std::collections::BTreeMap;
 use glium::Display;
+use threadpool::ThreadPool;
+use timer::Ms;
+use render::Renderer;
+use event::{Event, Update, EventStream};
+use vfs::{Vfs, Directory};
+
+
+// Name of the `Event::Something` sent for a reloaded resource, with its path.
+pub const RELOADED: &'static str = "resource-reloaded";
+const POLL: Ms = 500;
 
 
 pub type Map<T> = BTreeMap<PathBuf, Weak<T>>;
 
 
-pub trait Resource {
-    fn load(&Display, &Path) -> Self;
+#[derive(Clone, Debug)]
+pub enum ResourceError {
+    NotFound(PathBuf),
+    Decode(PathBuf, String),
+    Upload(PathBuf, String),
+}
+
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResourceError::NotFound(ref path) =>
+                write!(f, "{}: not found", path.display()),
+            ResourceError::Decode(ref path, ref e) =>
+                write!(f, "{}: cannot decode: {}", path.display(), e),
+            ResourceError::Upload(ref path, ref e) =>
+                write!(f, "{}: cannot upload: {}", path.display(), e),
+        }
+    }
+}
+
+
+// For text resources.
+pub fn utf8(path: &Path, data: Vec<u8>) -> Result<String, ResourceError> {
+    String::from_utf8(data)
+        .map_err(|e| ResourceError::Decode(path.to_path_buf(), e.to_string()))
+}
+
+
+// Built from the content of the file at `path`, read by the manager through its `Vfs`.
+pub trait Resource: Sized {
+    fn load(&Display, &Path, Vec<u8>) -> Result<Self, ResourceError>;
+
+    // With the content of `<file>.meta` when there is one, for the options of the resource.
+    fn load_meta(display: &Display, path: &Path, data: Vec<u8>, _: Vec<u8>)
+        -> Result<Self, ResourceError>
+    {
+        Self::load(display, path, data)
+    }
+}
+
+
+// Where the metadata of the file at `path` is.
+pub fn meta_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".meta");
+    PathBuf::from(name)
+}
+
+
+// The file and its metadata, which is optional.
+fn read(vfs: &Vfs, path: &Path) -> Result<(Vec<u8>, Option<Vec<u8>>), ResourceError> {
+    let data = try!(vfs.read(path));
+    match vfs.read(&meta_path(path)) {
+        Ok (meta) => Ok ((data, Some (meta))),
+        Err (ResourceError::NotFound(_)) => Ok ((data, None)),
+        Err (e) => Err (e),
+    }
+}
+
+
+// Of the file or of its metadata, the latest.
+fn modified(vfs: &Vfs, key: &Path) -> Option<SystemTime> {
+    let file = vfs.modified(key);
+    let meta = vfs.modified(&meta_path(key));
+    if meta > file { meta } else { file }
+}
+
+
+// Without a file, from embedded or in-memory data. `name` is only used in errors.
+pub fn from_bytes<T: Resource>(display: &Display, name: &str, data: &[u8])
+    -> Result<T, ResourceError>
+{
+    T::load(display, Path::new(name), data.to_vec())
+}
+
+
+pub fn from_reader<T: Resource, R: io::Read>(display: &Display, name: &str, reader: R)
+    -> Result<T, ResourceError>
+{
+    T::load(display, Path::new(name), try!(read_all(name, reader)))
+}
+
+
+// Only the decoding, without a display: what `AsyncResource::upload` gets.
+pub fn decode_bytes<T: AsyncResource>(name: &str, data: &[u8]) -> Result<T::Data, ResourceError> {
+    T::decode(Path::new(name), data.to_vec())
+}
+
+
+pub fn decode_reader<T: AsyncResource, R: io::Read>(name: &str, reader: R)
+    -> Result<T::Data, ResourceError>
+{
+    T::decode(Path::new(name), try!(read_all(name, reader)))
+}
+
+
+fn read_all<R: io::Read>(name: &str, mut reader: R) -> Result<Vec<u8>, ResourceError> {
+    let mut data = Vec::new();
+    match reader.read_to_end(&mut data) {
+        Ok (_) => Ok (data),
+        Err (e) => Err (ResourceError::Decode(PathBuf::from(name), e.to_string())),
+    }
+}
+
+
+// Text files, shader sources for instance, see `Renderer::set_shader`.
+impl Resource for String {
+    fn load(_: &Display, path: &Path, data: Vec<u8>) -> Result<String, ResourceError> {
+        utf8(path, data)
+    }
+}
+
+
+// A resource which follows its reloads, see `Manager::live`.
+pub struct Live<T> {
+    cell: Rc<RefCell<Rc<T>>>,
+}
+
+
+impl<T> Clone for Live<T> {
+    fn clone(&self) -> Live<T> {
+        Live { cell: self.cell.clone() }
+    }
+}
+
+
+impl<T> Live<T> {
+    // The version loaded last, get it again rather than keeping it.
+    pub fn get(&self) -> Rc<T> {
+        self.cell.borrow().clone()
+    }
+}
+
+
+// Loading split in two: decoding runs on a worker thread,
+// the upload needs the display and runs on the main thread.
+pub trait AsyncResource: Resource {
+    type Data: Send + 'static;
+
+    fn decode(&Path, Vec<u8>) -> Result<Self::Data, ResourceError>;
+    fn upload(&Display, &Path, Self::Data) -> Result<Self, ResourceError>;
+
+    fn decode_meta(path: &Path, data: Vec<u8>, _: Vec<u8>) -> Result<Self::Data, ResourceError> {
+        Self::decode(path, data)
+    }
+}
+
+
+enum Slot<T> {
+    Loading,
+    Ready(Rc<T>),
+    Failed(ResourceError),
+}
+
+
+// A resource being loaded by `Manager::load_async`.
+pub struct Handle<T> {
+    slot: Rc<RefCell<Slot<T>>>,
+}
+
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Handle<T> {
+        Handle { slot: self.slot.clone() }
+    }
+}
+
+
+impl<T> Handle<T> {
+    fn new(slot: Slot<T>) -> Handle<T> {
+        Handle { slot: Rc::new(RefCell::new(slot)) }
+    }
+
+    fn set(&self, slot: Slot<T>) {
+        *self.slot.borrow_mut() = slot;
+    }
+
+    // Loaded or failed.
+    pub fn is_done(&self) -> bool {
+        match *self.slot.borrow() {
+            Slot::Loading => false,
+            _ => true,
+        }
+    }
+
+    pub fn get(&self) -> Option<Rc<T>> {
+        match *self.slot.borrow() {
+            Slot::Ready(ref x) => Some (x.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<ResourceError> {
+        match *self.slot.borrow() {
+            Slot::Failed(ref e) => Some (e.clone()),
+            _ => None,
+        }
+    }
+}
+
+
+// Worker threads and the uploads waiting for the main thread, owned by `Engine`
+// and shared by the managers through `Manager::loader`.
+#[derive(Clone)]
+pub struct Loader {
+    pool: ThreadPool,
+    // Each returns true once its upload is done.
+    uploads: Rc<RefCell<Vec<Box<FnMut(&Display) -> bool>>>>,
+    total: Rc<Cell<usize>>,
+    done: Rc<Cell<usize>>,
+}
+
+
+impl Loader {
+    pub fn new(threads: usize) -> Loader {
+        Loader {
+            pool: ThreadPool::new(threads),
+            uploads: Rc::new(RefCell::new(Vec::new())),
+            total: Rc::new(Cell::new(0)),
+            done: Rc::new(Cell::new(0)),
+        }
+    }
+
+    // Uploads what the workers have decoded, at most `budget` of them,
+    // so that a frame is not spent uploading every big atlas at once.
+    pub fn upload(&self, display: &Display, budget: usize) {
+        let mut uploads = self.uploads.borrow_mut();
+        let mut count = 0;
+        let mut i = 0;
+        while i < uploads.len() && count < budget {
+            if (uploads[i])(display) {
+                uploads.remove(i);
+                self.done.set(self.done.get() + 1);
+                count += 1;
+            }
+            else {
+                i += 1;
+            }
+        }
+    }
+
+    // Loads done and started, for a loading screen.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.done.get(), self.total.get())
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.uploads.borrow().is_empty()
+    }
 }
 
 
 pub struct Manager<'a, T: Resource> {
-    pub map: RefCell<Map<T>>,
+    pub map: Rc<RefCell<Map<T>>>,
     path: PathBuf,
     display: &'a Display,
+    // Handed out instead of a resource failing to load, see `get_or_placeholder`.
+    placeholder: Option<Rc<T>>,
+    // Modification times of the loaded files, when watching.
+    stamps: Option<Rc<RefCell<BTreeMap<PathBuf, SystemTime>>>>,
+    since_poll: Ms,
+    // Loads started by `load_async` and not uploaded yet.
+    pending: Rc<RefCell<BTreeMap<PathBuf, Handle<T>>>>,
+    loader: Option<Loader>,
+    vfs: Vfs,
+    // Handles swapped to the new resource when it is loaded again.
+    lives: RefCell<BTreeMap<PathBuf, Vec<Weak<RefCell<Rc<T>>>>>>,
 }
 
 
 impl<'a, T: Resource> Manager<'a, T> {
     pub fn new(display: &'a Display, path: PathBuf) -> Manager<'a, T> {
         Manager {
-            map: RefCell::new(Map::new()),
+            map: Rc::new(RefCell::new(Map::new())),
             path: path,
             display: display,
+            placeholder: None,
+            stamps: None,
+            since_poll: 0,
+            pending: Rc::new(RefCell::new(BTreeMap::new())),
+            loader: None,
+            vfs: Vfs::new().mount("", Directory::new("")),
+            lives: RefCell::new(BTreeMap::new()),
         }
     }
 
-    fn load(&self, key: &PathBuf) -> Rc<T> {
-        let mut map = self.map.borrow_mut();
-        if map.contains_key(key) { let _ = map.remove(key); }
-        let res = Rc::new(T::load(self.display, &key));
-        map.insert(key.clone(), Rc::downgrade(&res));
-        return res;
+    // Where files are read, the working directory by default.
+    pub fn vfs(self, vfs: &Vfs) -> Manager<'a, T> {
+        Manager { vfs: vfs.clone(), ..self }
     }
 
-    pub fn get<P: AsRef<Path>>(&self, p: P) -> Rc<T> {
+    pub fn loader(self, loader: &Loader) -> Manager<'a, T> {
+        Manager { loader: Some (loader.clone()), ..self }
+    }
+
+    // Reload files changed on disk, see `impl Update`.
+    pub fn watch(self) -> Manager<'a, T> {
+        Manager { stamps: Some (Rc::new(RefCell::new(BTreeMap::new()))), ..self }
+    }
+
+    pub fn placeholder(self, placeholder: T) -> Manager<'a, T> {
+        Manager { placeholder: Some (Rc::new(placeholder)), ..self }
+    }
+
+    // The entry of `key` is only replaced once the new one is loaded,
+    // a failed reload leaves it in place to be polled again.
+    fn load(&self, key: &PathBuf) -> Result<Rc<T>, ResourceError> {
+        let res = Rc::new(match try!(read(&self.vfs, key)) {
+            (data, Some (meta)) => try!(T::load_meta(self.display, &key, data, meta)),
+            (data, None) => try!(T::load(self.display, &key, data)),
+        });
+        self.map.borrow_mut().insert(key.clone(), Rc::downgrade(&res));
+        if let Some(lives) = self.lives.borrow_mut().get_mut(key) {
+            lives.retain(|x| match x.upgrade() {
+                Some (cell) => { *cell.borrow_mut() = res.clone(); true }
+                None => false,
+            });
+        }
+        if let Some(ref stamps) = self.stamps {
+            if let Some(time) = modified(&self.vfs, key) {
+                stamps.borrow_mut().insert(key.clone(), time);
+            }
+        }
+        return Ok (res);
+    }
+
+    // Files in use that changed since they were loaded, loaded again.
+    // A file that fails to load is left as it is and tried again next time.
+    pub fn poll(&self) -> Vec<PathBuf> {
+        let stamps = match self.stamps {
+            Some (ref x) => x,
+            None => return Vec::new(),
+        };
+        let changed: Vec<PathBuf> = {
+            let map = self.map.borrow();
+            let stamps = stamps.borrow();
+            map.iter()
+                .filter(|&(_, weak)| weak.upgrade().is_some())
+                .filter(|&(key, _)| {
+                    let time = modified(&self.vfs, key);
+                    time.is_some() && time != stamps.get(key).cloned()
+                })
+                .map(|(key, _)| key.clone())
+                .collect()
+        };
+        changed.into_iter()
+            .filter(|key| self.load(key).is_ok())
+            .map(|key| key.strip_prefix(&self.path).map(|x| x.to_path_buf()).unwrap_or(key))
+            .collect()
+    }
+
+    // Load again from disk. `Live` handles get the new one, the `Rc`s already
+    // given out keep the old one: get it again on `RELOADED` for instance.
+    pub fn reload<P: AsRef<Path>>(&self, p: P) -> Result<Rc<T>, ResourceError> {
+        self.load(&self.path.join(p))
+    }
+
+    // Like `get`, but the handle is swapped to the new resource on each reload.
+    pub fn live<P: AsRef<Path>>(&self, p: P) -> Result<Live<T>, ResourceError> {
+        let res = try!(self.get(p.as_ref()));
+        let cell = Rc::new(RefCell::new(res));
+        let key = self.path.join(p);
+        self.lives.borrow_mut().entry(key).or_insert(Vec::new()).push(Rc::downgrade(&cell));
+        Ok (Live { cell: cell })
+    }
+
+    // The placeholder if the resource cannot be loaded, panics without a placeholder.
+    pub fn get_or_placeholder<P: AsRef<Path>>(&self, p: P) -> Rc<T> {
+        match self.get(p) {
+            Ok (x) => x,
+            Err (e) => match self.placeholder {
+                Some (ref x) => {
+                    use std::io::Write;
+                    let _ = writeln!(io::stderr(), "{}, using the placeholder", e);
+                    x.clone()
+                }
+                None => panic!("{}", e),
+            }
+        }
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, p: P) -> Result<Rc<T>, ResourceError> {
         let key = self.path.join(p);
         match {
             let map = self.map.borrow();
@@ -51,8 +418,88 @@ impl<'a, T: Resource> Manager<'a, T> {
             }
         } {
             None => self.load(&key),
-            Some (x) => x,
+            Some (x) => Ok (x),
         }
     }
 }
 
+
+impl<'a, T: AsyncResource + 'static> Manager<'a, T> {
+    // Decodes on the loader's workers, the handle is ready after some `Engine::update`.
+    // Without a loader, the resource is loaded right away. Loading a key
+    // already on its way gives the same handle.
+    pub fn load_async<P: AsRef<Path>>(&self, p: P) -> Handle<T> {
+        let key = self.path.join(p.as_ref());
+        let loader = match self.loader {
+            Some (ref x) => x,
+            None => return match self.get(p) {
+                Ok (x) => Handle::new(Slot::Ready(x)),
+                Err (e) => Handle::new(Slot::Failed(e)),
+            },
+        };
+        if let Some(x) = self.map.borrow().get(&key).and_then(|x| x.upgrade()) {
+            return Handle::new(Slot::Ready(x));
+        }
+        if let Some(x) = self.pending.borrow().get(&key) {
+            return x.clone();
+        }
+        let (sender, receiver) = channel();
+        let path = key.clone();
+        let vfs = self.vfs.clone();
+        loader.pool.execute(move || {
+            // Before reading, a change while it is read is seen by the next poll.
+            let time = modified(&vfs, &path);
+            let _ = sender.send((time, read(&vfs, &path).and_then(|x| match x {
+                (data, Some (meta)) => T::decode_meta(&path, data, meta),
+                (data, None) => T::decode(&path, data),
+            })));
+        });
+        let handle = Handle::new(Slot::Loading);
+        self.pending.borrow_mut().insert(key.clone(), handle.clone());
+        let map = self.map.clone();
+        let stamps = self.stamps.clone();
+        let pending = self.pending.clone();
+        let slot = handle.clone();
+        let receiver: Receiver<(Option<SystemTime>, Result<T::Data, ResourceError>)> = receiver;
+        loader.total.set(loader.total.get() + 1);
+        loader.uploads.borrow_mut().push(box move |display: &Display| {
+            let (time, data) = match receiver.try_recv() {
+                Ok (x) => x,
+                Err (::std::sync::mpsc::TryRecvError::Empty) => return false,
+                Err (_) => (None, Err (ResourceError::Decode(key.clone(), "worker died".to_string()))),
+            };
+            pending.borrow_mut().remove(&key);
+            match data.and_then(|x| T::upload(display, &key, x)) {
+                Ok (x) => {
+                    let res = Rc::new(x);
+                    map.borrow_mut().insert(key.clone(), Rc::downgrade(&res));
+                    // Like `load`, so that `poll` does not load it again right away.
+                    if let (Some (stamps), Some (time)) = (stamps.as_ref(), time) {
+                        stamps.borrow_mut().insert(key.clone(), time);
+                    }
+                    slot.set(Slot::Ready(res));
+                }
+                Err (e) => slot.set(Slot::Failed(e)),
+            }
+            true
+        });
+        handle
+    }
+}
+
+
+// Polls the watched files now and then, sends `RELOADED` for each one loaded again.
+impl<'a, T: Resource> Update for Manager<'a, T> {
+    fn update(&mut self, _: &Renderer, delta: Ms, mut stream: EventStream)
+        -> EventStream
+    {
+        if self.stamps.is_none() { return stream }
+        self.since_poll += delta;
+        if self.since_poll < POLL { return stream }
+        self.since_poll = 0;
+        for path in self.poll() {
+            stream.push(Event::Something(RELOADED.to_string(), box path));
+        }
+        return stream;
+    }
+}
@@ -0,0 +1,177 @@
+use std::rc::Rc;
+use std::collections::BTreeMap;
+use id::Id;
+use timer::Ms;
+use event::{Event, Update, EventStream};
+use render::Renderer;
+
+
+// Returned when scheduling, to cancel the timer later.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct Handle(Id);
+
+
+struct Job {
+    due: Ms,
+    every: Option<Ms>,
+    f: Rc<Fn() -> Event>,
+}
+
+
+// Timers firing events into the stream, once or periodically.
+pub struct Scheduler {
+    now: Ms,
+    jobs: BTreeMap<Handle, Job>,
+}
+
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler { now: 0, jobs: BTreeMap::new() }
+    }
+
+    fn add(&mut self, due: Ms, every: Option<Ms>, f: Rc<Fn() -> Event>) -> Handle {
+        let handle = Handle(Id::new());
+        self.jobs.insert(handle, Job { due: self.now + due, every: every, f: f });
+        handle
+    }
+
+    // Sends `f()` once, `ms` from now.
+    pub fn after<F>(&mut self, ms: Ms, f: F) -> Handle
+            where F: Fn() -> Event + 'static {
+        self.add(ms, None, Rc::new(f))
+    }
+
+    // Sends `f()` every `ms` until cancelled.
+    pub fn every<F>(&mut self, ms: Ms, f: F) -> Handle
+            where F: Fn() -> Event + 'static {
+        assert!(ms > 0);
+        self.add(ms, Some(ms), Rc::new(f))
+    }
+
+    // False if the timer already fired or was cancelled.
+    pub fn cancel(&mut self, handle: Handle) -> bool {
+        self.jobs.remove(&handle).is_some()
+    }
+
+    pub fn pending(&self, handle: Handle) -> bool {
+        self.jobs.contains_key(&handle)
+    }
+
+    pub fn clear(&mut self) {
+        self.jobs.clear();
+    }
+
+    // Events of the timers due within `delta`, in the order they fall due.
+    fn advance(&mut self, delta: Ms) -> Vec<Event> {
+        self.now += delta;
+        let mut due: Vec<(Ms, Handle)> = Vec::new();
+        for (handle, job) in &self.jobs {
+            // A periodic timer fires as many times as it should have.
+            let mut time = job.due;
+            while time <= self.now {
+                due.push((time, *handle));
+                match job.every {
+                    Some (every) => time += every,
+                    None => break,
+                }
+            }
+        }
+        due.sort();
+        let mut events = Vec::with_capacity(due.len());
+        for &(_, handle) in &due {
+            events.push((self.jobs[&handle].f)());
+        }
+        let now = self.now;
+        let done: Vec<Handle> = self.jobs.iter_mut()
+            .filter_map(|(handle, job)| {
+                if job.due > now { return None }
+                match job.every {
+                    Some (every) => {
+                        job.due += (now - job.due) / every * every + every;
+                        None
+                    }
+                    None => Some (*handle),
+                }
+            })
+            .collect();
+        for handle in done {
+            self.jobs.remove(&handle);
+        }
+        events
+    }
+}
+
+
+impl Update for Scheduler {
+    fn update(&mut self, _: &Renderer, delta: Ms, mut stream: EventStream)
+        -> EventStream
+    {
+        for event in self.advance(delta) {
+            stream.push(event);
+        }
+        return stream;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use event::Event;
+    use super::Scheduler;
+
+    fn messages(events: Vec<Event>) -> Vec<String> {
+        events.into_iter().map(|x| match x {
+            Event::Message(x) => x,
+            _ => panic!("not a message"),
+        }).collect()
+    }
+
+    fn message(text: &str) -> Event {
+        Event::Message(text.to_string())
+    }
+
+    #[test]
+    fn order() {
+        let mut scheduler = Scheduler::new();
+        scheduler.after(30, || message("c"));
+        scheduler.after(10, || message("a"));
+        scheduler.after(20, || message("b"));
+        assert!(messages(scheduler.advance(5)).is_empty());
+        assert_eq!(messages(scheduler.advance(25)), vec!["a", "b", "c"]);
+        assert!(messages(scheduler.advance(100)).is_empty());
+    }
+
+    #[test]
+    fn catch_up() {
+        let mut scheduler = Scheduler::new();
+        let tick = scheduler.every(10, || message("tick"));
+        scheduler.after(25, || message("once"));
+        assert_eq!(messages(scheduler.advance(35)), vec!["tick", "tick", "once", "tick"]);
+        // Due again at 40, not 45.
+        assert!(messages(scheduler.advance(4)).is_empty());
+        assert_eq!(messages(scheduler.advance(1)), vec!["tick"]);
+        assert!(scheduler.pending(tick));
+    }
+
+    #[test]
+    fn cancel() {
+        let mut scheduler = Scheduler::new();
+        let once = scheduler.after(10, || message("once"));
+        let tick = scheduler.every(10, || message("tick"));
+        assert!(scheduler.cancel(once));
+        assert!(!scheduler.cancel(once));
+        assert_eq!(messages(scheduler.advance(10)), vec!["tick"]);
+        assert!(scheduler.cancel(tick));
+        assert!(messages(scheduler.advance(100)).is_empty());
+    }
+
+    #[test]
+    fn after_zero() {
+        let mut scheduler = Scheduler::new();
+        let now = scheduler.after(0, || message("now"));
+        assert_eq!(messages(scheduler.advance(0)), vec!["now"]);
+        assert!(!scheduler.pending(now));
+        assert!(messages(scheduler.advance(0)).is_empty());
+    }
+}
@@ -1,10 +1,10 @@
 #version 140
-uniform vec4 color_multiply;
 uniform sampler2D tex;
 in vec2 f_tex_coords;
+in vec4 f_color_multiply;
 out vec4 f_color;
 
 
 void main() {
-    f_color = texture(tex, f_tex_coords) * color_multiply;
+    f_color = texture(tex, f_tex_coords) * f_color_multiply;
 }
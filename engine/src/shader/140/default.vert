@@ -2,10 +2,12 @@
 uniform mat4 matrix;
 in vec2 position;
 in vec2 tex_coords;
+in vec4 color;
 out vec2 f_tex_coords;
+out vec4 f_color_multiply;
 
 void main() {
     gl_Position = matrix * vec4(position, 0.0, 1.0);
     f_tex_coords = tex_coords;
+    f_color_multiply = color;
 }
-
@@ -1,18 +1,17 @@
 use std::rc::Rc;
 use glium::{Display, Surface, Frame};
-use color::Color;
 use render::{Renderable, Renderer};
 use texture::Texture;
 use mesh::{VertexBuffer, IndexBuffer, Polygon, Mesh};
 use math::Mat;
 use sprite::Sprite;
+use profiler;
 
 
 
 pub struct Batch {
     texture: Rc<Texture>,
     mesh: Mesh,
-    color_multiply: Color,
 }
 
 
@@ -20,6 +19,7 @@ impl Batch {
     pub fn from_sprites(display: &Display, sprites: &[&Sprite]) -> Batch {
         use glium::index::PrimitiveType;
 
+        let _scope = profiler::scope("batch build");
         let len = sprites.len();
 
         if len == 0 { panic!() }
@@ -53,7 +53,6 @@ impl Batch {
                                                &ib[..]).unwrap(),
                 vertex_buffer: vb,
             },
-            color_multiply: first.color_multiply,
         }
     }
 }
@@ -64,8 +63,7 @@ impl Renderable for Batch {
         renderer.draw(target, &self.mesh,
             &uniform! {
                 matrix: *parent,
-                color_multiply: self.color_multiply,
-                tex: &self.texture.data
+                tex: &*self.texture
             }
         );
     }
@@ -0,0 +1,86 @@
+use std::rc::Rc;
+use std::collections::BTreeMap;
+use na::Vec2;
+use timer::Ms;
+use timeline::Loop;
+
+
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub offset: Vec2<f32>,
+    pub ms: Ms,
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Clip {
+    pub mode: Loop,
+    pub frames: Vec<Frame>,
+}
+
+
+impl Clip {
+    pub fn duration(&self) -> Ms {
+        self.frames.iter().map(|x| x.ms).fold(0, |a, b| a + b)
+    }
+
+    // The frame shown `now` ms after the clip started.
+    pub fn frame(&self, now: Ms) -> &Frame {
+        let total = self.duration();
+        let last = self.frames.len() - 1;
+        if total == 0 || last == 0 { return &self.frames[0] }
+        let mut t = match self.mode {
+            Loop::Once => if now >= total { return &self.frames[last] } else { now },
+            Loop::Repeat => now % total,
+            Loop::PingPong => {
+                // The end frames are not shown twice in a row.
+                let back = total - self.frames[0].ms - self.frames[last].ms;
+                let t = now % (total + back);
+                if t < total { t }
+                else {
+                    let mut t = t - total;
+                    for frame in self.frames[1..last].iter().rev() {
+                        if t < frame.ms { return frame }
+                        t -= frame.ms;
+                    }
+                    return &self.frames[0];
+                }
+            }
+        };
+        for frame in &self.frames {
+            if t < frame.ms { return frame }
+            t -= frame.ms;
+        }
+        &self.frames[last]
+    }
+}
+
+
+// Named clips of a sprite sheet, see `Sheet`.
+#[derive(Clone, Debug)]
+pub struct Clips {
+    pub map: BTreeMap<String, Rc<Clip>>,
+}
+
+
+impl Clips {
+    pub fn get(&self, name: &str) -> Option<Rc<Clip>> {
+        self.map.get(name).cloned()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use timeline::Loop;
+    use super::{Clip, Frame};
+
+    #[test]
+    fn frames() {
+        let frame = |x, ms| Frame { offset: na![x, 0.0], ms: ms };
+        let walk = Clip { mode: Loop::PingPong, frames: vec![frame(0.0, 100), frame(32.0, 100), frame(64.0, 200)] };
+        // 0, 1, 2, then back through 1 only.
+        let offsets: Vec<f32> = [0, 100, 200, 450, 500].iter().map(|&x| walk.frame(x).offset.x).collect();
+        assert_eq!(offsets, vec![0.0, 32.0, 64.0, 32.0, 0.0]);
+    }
+}
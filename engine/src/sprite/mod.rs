@@ -6,16 +6,22 @@ use color::Color;
 use render::{Renderable, Renderer};
 use texture::Texture;
 use mesh::Polygon;
-use event::{Update, EventStream};
+use event::{Event, Update, EventStream};
 use transform::Transform;
 use math::Mat;
 use timer::Ms;
 use animation::State;
+use timeline::{Animatable, Lens};
 use self::rectangle::Rectangle;
+use self::clip::Clip;
+use self::sheet::{Sheet, Region};
 
 pub mod animate;
 pub mod batch;
+pub mod clip;
 pub mod rectangle;
+pub mod sheet;
+pub mod tilemap;
 
 #[derive(Clone)]
 pub struct Sprite {
@@ -23,9 +29,16 @@ pub struct Sprite {
     pub transform: Transform,
     pub texture_offset: Vec2<f32>,
     pub color_multiply: Color,
+    // Over `color_multiply`, so that a flash does not undo a tint set elsewhere.
+    pub tint: Color,
+    // Position before the last update, see `interpolated`.
+    previous: Vec2<f32>,
     texture: Rc<Texture>,
     texture_clip_size: Vec2<f32>,
     state: State<Sprite>,
+    // Sprite-sheet clip and the time it has been playing,
+    // runs alongside `state` so that frames go on while the sprite moves.
+    clip: Option<(Rc<Clip>, Ms)>,
 }
 
 
@@ -40,7 +53,10 @@ impl Sprite {
             texture_clip_size: na::cast(clip_size),
             texture: texture,
             color_multiply: Color::white(),
+            tint: Color::white(),
             texture_offset: na::zero(),
+            previous: na::zero(),
+            clip: None,
         }
     }
 
@@ -48,8 +64,28 @@ impl Sprite {
         Sprite { texture_offset: na::cast(offset), ..self }
     }
 
+    // The part of the texture shown, by name from a `Sheet` instead of an offset.
+    // `size` is taken as that of the untrimmed frame, the trimmed part is
+    // shown at its share of it and its pivot put on the position.
+    pub fn region(self, region: &Region) -> Sprite {
+        let clip: Vec2<f32> = na::cast(region.size);
+        let source: Vec2<f32> = na::cast(region.source);
+        let sprite = Sprite {
+            size: self.size * clip / source,
+            texture_offset: na::cast(region.offset),
+            texture_clip_size: clip,
+            ..self
+        };
+        sprite.anchor(region.anchor())
+    }
+
+    // The region `name` of `sheet`, `size` being that of its untrimmed frame.
+    pub fn named(sheet: &Sheet, name: &str, texture: Rc<Texture>, size: Vec2<i32>) -> Option<Sprite> {
+        sheet.region(name).map(|x| Sprite::new(size, x.size, texture).region(x))
+    }
+
     fn batchable(&self, other: &Sprite) -> bool {
-        self.texture == other.texture && self.color_multiply == other.color_multiply
+        self.texture == other.texture
     }
 
     #[inline]
@@ -68,7 +104,15 @@ impl Sprite {
 
     pub fn position(self, position: Vec2<f32>) -> Sprite {
         let transform = self.transform.position(position);
-        Sprite { transform: transform, ..self }
+        Sprite { transform: transform, previous: position, ..self }
+    }
+
+    // A copy between the two last updates, `alpha` from `ProgramTimer::alpha`.
+    pub fn interpolated(&self, alpha: f32) -> Sprite {
+        let mut sprite = self.clone();
+        let position = self.transform.position;
+        sprite.transform.position = self.previous + (position - self.previous) * alpha;
+        sprite
     }
 
     pub fn state(self, state: State<Sprite>) -> Sprite {
@@ -78,6 +122,87 @@ impl Sprite {
     pub fn set_state(&mut self, state: State<Sprite>) {
         self.state = state;
     }
+
+    // Snap the animation to its end, returns the events it would have sent.
+    pub fn finish(&mut self) -> Vec<Event> {
+        use std::mem::swap;
+
+        let mut events = Vec::new();
+        let mut state = State::Nil;
+        swap(&mut self.state, &mut state);
+        state.finish(self, &mut events);
+        events
+    }
+
+    pub fn cancel(&mut self) {
+        self.state.cancel();
+    }
+
+    // Starts `clip` as if it had been playing for `from` ms.
+    pub fn play(&mut self, clip: Rc<Clip>, from: Ms) {
+        self.texture_offset = clip.frame(from).offset;
+        self.clip = Some ((clip, from));
+    }
+
+    // The clip played and how long it has been playing.
+    pub fn clip(&self) -> Option<(Rc<Clip>, Ms)> {
+        self.clip.clone()
+    }
+
+    pub fn stop(&mut self) {
+        self.clip = None;
+    }
+
+    pub fn animating(&self) -> bool {
+        self.clip.is_some()
+    }
+
+    // Its state is running, the clip aside.
+    pub fn playing(&self) -> bool {
+        match self.state {
+            State::Nil => false,
+            _ => true,
+        }
+    }
+
+    fn step(&mut self, delta: Ms) {
+        use timeline::Loop;
+
+        let done = match self.clip {
+            Some ((ref clip, ref mut time)) => {
+                *time += delta;
+                self.texture_offset = clip.frame(*time).offset;
+                clip.mode == Loop::Once && *time >= clip.duration()
+            }
+            None => false,
+        };
+        if done { self.clip = None }
+    }
+}
+
+
+
+impl Animatable for Sprite {
+    fn lens(property: &str) -> Option<Lens<Sprite>> {
+        fn position(x: &mut Sprite) -> &mut Vec2<f32> { &mut x.transform.position }
+        fn offset(x: &mut Sprite) -> &mut Vec2<f32> { &mut x.transform.offset }
+        fn scale(x: &mut Sprite) -> &mut f32 { &mut x.transform.scale }
+        fn alpha(x: &mut Sprite) -> &mut f32 { &mut x.color_multiply.a }
+        fn color(x: &mut Sprite) -> &mut Color { &mut x.color_multiply }
+        fn tint(x: &mut Sprite) -> &mut Color { &mut x.tint }
+        fn texture(x: &mut Sprite) -> &mut Vec2<f32> { &mut x.texture_offset }
+
+        Some (match property {
+            "position" => Lens::Vector(position),
+            "offset" => Lens::Vector(offset),
+            "scale" => Lens::Scalar(scale),
+            "alpha" => Lens::Scalar(alpha),
+            "color" => Lens::Color(color),
+            "tint" => Lens::Color(tint),
+            "texture_offset" => Lens::Vector(texture),
+            _ => return None,
+        })
+    }
 }
 
 
@@ -88,8 +213,7 @@ impl Renderable for Sprite {
         renderer.draw(target, &rect.mesh(renderer.display),
             &uniform! {
                 matrix: *parent,
-                color_multiply: self.color_multiply,
-                tex: &self.texture.data,
+                tex: &*self.texture,
             }
         );
     }
@@ -98,18 +222,24 @@ impl Renderable for Sprite {
 
 
 impl Update for Sprite {
-    fn update(&mut self, _: &Renderer, delta: Ms, stream: EventStream)
+    fn update(&mut self, _: &Renderer, delta: Ms, mut stream: EventStream)
         -> EventStream
     {
         use std::mem::swap;
         use animation::Return;
 
+        self.previous = self.transform.position;
+        self.step(delta);
+        let mut events = Vec::new();
         let mut state = State::Nil;
         swap(&mut self.state, &mut state);
-        if let Return::Become(x) = state.transition(self, delta) {
+        if let Return::Become(x) = state.transition(self, delta, &mut events) {
             state = x;
         }
         self.state = state;
+        for event in events {
+            stream.push(event);
+        }
         return stream;
     }
 }
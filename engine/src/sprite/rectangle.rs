@@ -24,6 +24,7 @@ impl Rectangle {
                 Vertex {
                     position: sprite.transform.compute(na![$a, $b]),
                     tex_coords: [($c+i)/tex_w, 1.0-($d+j)/tex_h],
+                    color: (sprite.color_multiply * sprite.tint).as_array(),
                 }
             )
         }
@@ -0,0 +1,314 @@
+use na;
+use std::rc::Rc;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use glium::Display;
+use na::Vec2;
+use rustc_serialize::json::Json;
+use timer::Ms;
+use timeline::Loop;
+use texture::Texture;
+use sprite::clip::{Clip, Clips, Frame};
+use resources::{Manager, Resource, AsyncResource, ResourceError, utf8};
+
+
+// Frames without a duration, as TexturePacker exports them.
+const DEFAULT_MS: Ms = 100;
+
+
+// A named frame of a sheet, in pixels of its texture.
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub offset: Vec2<i32>,
+    pub size: Vec2<i32>,
+    // Size of the frame before it was trimmed, and where the trimmed part was in it.
+    pub source: Vec2<i32>,
+    pub trim: Vec2<i32>,
+    // In the untrimmed frame, from its top left, 0 to 1.
+    pub pivot: Vec2<f32>,
+}
+
+
+impl Region {
+    // For `Sprite::anchor`, puts the pivot on the position of the sprite.
+    pub fn anchor(&self) -> Vec2<f32> {
+        let size: Vec2<f32> = na::cast(self.size);
+        let source: Vec2<f32> = na::cast(self.source);
+        let trim: Vec2<f32> = na::cast(self.trim);
+        let x = (self.pivot.x * source.x - trim.x) / size.x;
+        let y = (self.pivot.y * source.y - trim.y) / size.y;
+        na![1.0 - 2.0 * x, 2.0 * y - 1.0]
+    }
+}
+
+
+fn missing(key: &str) -> String {
+    format!("missing {}", key)
+}
+
+
+fn int(json: &Json, key: &str) -> Result<i32, String> {
+    json.find(key).and_then(|x| x.as_i64()).map(|x| x as i32).ok_or(missing(key))
+}
+
+
+fn float(json: &Json, key: &str) -> Result<f32, String> {
+    json.find(key).and_then(|x| x.as_f64()).map(|x| x as f32).ok_or(missing(key))
+}
+
+
+// `{"x", "y", "w", "h"}` as offset and size.
+fn rect(json: &Json) -> Result<(Vec2<i32>, Vec2<i32>), String> {
+    Ok ((na![try!(int(json, "x")), try!(int(json, "y"))],
+         na![try!(int(json, "w")), try!(int(json, "h"))]))
+}
+
+
+fn frame(json: &Json) -> Result<(Region, Ms), String> {
+    if json.find("rotated").and_then(|x| x.as_boolean()) == Some (true) {
+        return Err ("rotated frames are not supported".to_string());
+    }
+    let (offset, size) = try!(rect(try!(json.find("frame").ok_or(missing("frame")))));
+    let trim = match json.find("spriteSourceSize") {
+        Some (x) => try!(rect(x)).0,
+        None => na::zero(),
+    };
+    let source = match json.find("sourceSize") {
+        Some (x) => na![try!(int(x, "w")), try!(int(x, "h"))],
+        None => size,
+    };
+    let pivot = match json.find("pivot") {
+        Some (x) => na![try!(float(x, "x")), try!(float(x, "y"))],
+        None => na![0.5, 0.5],
+    };
+    let ms = json.find("duration").and_then(|x| x.as_i64()).map_or(DEFAULT_MS, |x| x as Ms);
+    let region = Region { offset: offset, size: size, source: source, trim: trim, pivot: pivot };
+    Ok ((region, ms))
+}
+
+
+// Numbers in names compared as numbers, `walk 2` before `walk 10`.
+fn natural(name: &str) -> Vec<(String, u64)> {
+    let mut key = Vec::new();
+    let mut text = String::new();
+    let mut number: Option<u64> = None;
+    for c in name.chars() {
+        match c.to_digit(10) {
+            Some (d) => number = Some (number.unwrap_or(0) * 10 + d as u64),
+            None => {
+                if let Some(n) = number.take() {
+                    key.push((text.clone(), n));
+                    text.clear();
+                }
+                text.push(c);
+            }
+        }
+    }
+    key.push((text, number.unwrap_or(0)));
+    key
+}
+
+
+// Regions and clips of a sprite sheet, from the JSON of TexturePacker
+// (hash or array) or of Aseprite (with `frameTags`).
+// A clip only moves the texture offset, its frames should all have the same size.
+#[derive(Clone, Debug)]
+pub struct Sheet {
+    // As named in the sheet, see `texture`.
+    pub image: PathBuf,
+    pub regions: BTreeMap<String, Region>,
+    pub clips: Clips,
+}
+
+
+impl Sheet {
+    pub fn parse(text: &str) -> Result<Sheet, String> {
+        let json = match Json::from_str(text) {
+            Ok (x) => x,
+            Err (e) => return Err (format!("{}", e)),
+        };
+        // Frames in order: an array keeps it, the keys of a hash are sorted.
+        let mut frames: Vec<(String, Region, Ms)> = Vec::new();
+        match json.find("frames") {
+            Some (&Json::Array(ref xs)) => for x in xs {
+                let name = try!(x.find("filename").and_then(|x| x.as_string())
+                                .ok_or(missing("filename")));
+                let (region, ms) = try!(frame(x));
+                frames.push((name.to_string(), region, ms));
+            },
+            Some (&Json::Object(ref xs)) => {
+                let mut names: Vec<&String> = xs.keys().collect();
+                names.sort_by(|a, b| natural(a).cmp(&natural(b)));
+                for name in names {
+                    let (region, ms) = try!(frame(&xs[name]));
+                    frames.push((name.clone(), region, ms));
+                }
+            }
+            _ => return Err (missing("frames")),
+        }
+
+        let image = json.find_path(&["meta", "image"]).and_then(|x| x.as_string())
+            .map_or(PathBuf::new(), PathBuf::from);
+        let regions: BTreeMap<String, Region> = frames.iter()
+            .map(|&(ref name, ref region, _)| (name.clone(), region.clone()))
+            .collect();
+        let clip_frame = |i: usize| {
+            let (_, ref region, ms) = frames[i];
+            Frame { offset: na::cast(region.offset), ms: ms }
+        };
+
+        let mut clips = BTreeMap::new();
+        // Aseprite: frames `from` to `to` of the array.
+        if let Some(tags) = json.find_path(&["meta", "frameTags"]).and_then(|x| x.as_array()) {
+            for tag in tags {
+                let name = try!(tag.find("name").and_then(|x| x.as_string()).ok_or(missing("name")));
+                let (from, to) = (try!(int(tag, "from")) as usize, try!(int(tag, "to")) as usize);
+                if from > to || to >= frames.len() {
+                    return Err (format!("tag {} out of the frames", name));
+                }
+                let mut clip = Clip { mode: Loop::Repeat, frames: (from..to + 1).map(&clip_frame).collect() };
+                match tag.find("direction").and_then(|x| x.as_string()) {
+                    Some ("reverse") => clip.frames.reverse(),
+                    Some ("pingpong") => clip.mode = Loop::PingPong,
+                    _ => (),
+                }
+                clips.insert(name.to_string(), Rc::new(clip));
+            }
+        }
+        // TexturePacker: lists of frame names.
+        if let Some(animations) = json.find("animations").and_then(|x| x.as_object()) {
+            for (name, names) in animations {
+                let names = try!(names.as_array().ok_or(format!("animation {} is not a list", name)));
+                let mut clip = Clip { mode: Loop::Repeat, frames: Vec::new() };
+                for x in names {
+                    let i = try!(x.as_string()
+                        .and_then(|x| frames.iter().position(|&(ref name, _, _)| name == x))
+                        .ok_or(format!("animation {}: unknown frame {}", name, x)));
+                    clip.frames.push(clip_frame(i));
+                }
+                if clip.frames.is_empty() {
+                    return Err (format!("animation {} has no frame", name));
+                }
+                clips.insert(name.clone(), Rc::new(clip));
+            }
+        }
+        Ok (Sheet { image: image, regions: regions, clips: Clips { map: clips } })
+    }
+
+    pub fn region(&self, name: &str) -> Option<&Region> {
+        self.regions.get(name)
+    }
+
+    // The image of the sheet, `textures` being in the directory of the sheet.
+    pub fn texture(&self, textures: &Manager<Texture>) -> Result<Rc<Texture>, ResourceError> {
+        textures.get(&self.image)
+    }
+}
+
+
+impl Resource for Sheet {
+    fn load(display: &Display, path: &Path, data: Vec<u8>) -> Result<Sheet, ResourceError> {
+        let text = try!(Sheet::decode(path, data));
+        Sheet::upload(display, path, text)
+    }
+}
+
+
+// Holds clips, shared through `Rc`, so only the reading is done on the worker.
+impl AsyncResource for Sheet {
+    type Data = String;
+
+    fn decode(path: &Path, data: Vec<u8>) -> Result<String, ResourceError> {
+        utf8(path, data)
+    }
+
+    fn upload(_: &Display, path: &Path, text: String) -> Result<Sheet, ResourceError> {
+        Sheet::parse(&text).map_err(|e| ResourceError::Decode(path.to_path_buf(), e))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use resources::{decode_bytes, decode_reader};
+    use timeline::Loop;
+    use super::Sheet;
+
+    const BLOCK: &'static str = include_str!("../../../assets/tile/block.json");
+
+    // TexturePacker hash, trimmed with a pivot, and its lists of frames.
+    const PACKED: &'static str = r#"{
+        "frames": {
+            "walk 10": {"frame": {"x": 0, "y": 0, "w": 8, "h": 8}},
+            "walk 2": {"frame": {"x": 8, "y": 0, "w": 8, "h": 8}},
+            "coin": {
+                "frame": {"x": 16, "y": 0, "w": 10, "h": 20},
+                "spriteSourceSize": {"x": 5, "y": 0, "w": 10, "h": 20},
+                "sourceSize": {"w": 20, "h": 20},
+                "pivot": {"x": 0.5, "y": 1.0}
+            }
+        },
+        "animations": {"walk": ["walk 2", "walk 10"]},
+        "meta": {"image": "packed.png"}
+    }"#;
+
+    #[test]
+    fn aseprite() {
+        let sheet = Sheet::parse(BLOCK).unwrap();
+        assert_eq!(sheet.image, PathBuf::from("block.png"));
+        assert_eq!(sheet.region("river 1").unwrap().offset, na![256, 256]);
+        let river = sheet.clips.get("river").unwrap();
+        assert_eq!(river.mode, Loop::Repeat);
+        assert_eq!(river.frames.len(), 2);
+        assert_eq!(river.frames[1].offset, na![256.0, 256.0]);
+        assert_eq!(river.frames[1].ms, 700);
+    }
+
+    #[test]
+    fn texture_packer() {
+        let sheet = Sheet::parse(PACKED).unwrap();
+        assert_eq!(sheet.image, PathBuf::from("packed.png"));
+        // In the order of the list, default durations.
+        let walk = sheet.clips.get("walk").unwrap();
+        assert_eq!(walk.frames[0].offset, na![8.0, 0.0]);
+        assert_eq!(walk.frames[1].offset, na![0.0, 0.0]);
+        assert_eq!(walk.frames[0].ms, 100);
+    }
+
+    #[test]
+    fn from_bytes() {
+        let text = decode_bytes::<Sheet>("block.json", BLOCK.as_bytes()).unwrap();
+        let sheet = Sheet::parse(&text).unwrap();
+        assert_eq!(sheet.clips.get("lava").unwrap().frames[1].offset, na![256.0, 768.0]);
+        assert!(decode_bytes::<Sheet>("bad.json", &[0xff, 0xfe]).is_err());
+    }
+
+    #[test]
+    fn from_reader() {
+        let text = decode_reader::<Sheet, _>("packed.json", Cursor::new(PACKED)).unwrap();
+        assert_eq!(Sheet::parse(&text).unwrap().regions.len(), 3);
+    }
+
+    #[test]
+    fn anchor() {
+        let sheet = Sheet::parse(PACKED).unwrap();
+        // Untrimmed and centered.
+        assert_eq!(sheet.region("walk 2").unwrap().anchor(), na![0.0, 0.0]);
+        // The bottom middle of the 20 px frame, the trimmed part starts 5 px in.
+        assert_eq!(sheet.region("coin").unwrap().anchor(), na![0.0, 1.0]);
+    }
+
+    #[test]
+    fn errors() {
+        let bad = ["", "{}", r#"{"frames": [{"filename": "a"}]}"#,
+                   r#"{"frames": [{"filename": "a", "rotated": true,
+                                   "frame": {"x": 0, "y": 0, "w": 1, "h": 1}}]}"#,
+                   r#"{"frames": [], "meta": {"frameTags": [{"name": "a", "from": 0, "to": 1}]}}"#,
+                   r#"{"frames": {}, "animations": {"a": ["b"]}}"#];
+        for text in bad.iter() {
+            assert!(Sheet::parse(text).is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::rc::Rc;
+use std::cell::Cell as Shared;
+use std::collections::BTreeMap;
+use glium::{Display, Frame};
+use na::Vec2;
+use render::{Renderable, Renderer};
+use event::{Update, EventStream};
+use texture::Texture;
+use mesh::{Vertex, VertexBuffer, IndexBuffer, Mesh};
+use math::Mat;
+use timer::Ms;
+use sprite::Sprite;
+use profiler;
+
+
+pub type Cell = [i32; 3];
+
+
+struct Chunk {
+    cells: BTreeMap<Cell, Vec<Sprite>>,
+    mesh: Option<Mesh>,
+    bound: (Vec2<f32>, Vec2<f32>),
+    dirty: bool,
+    // Some sprite plays a clip, see `Sprite::play`.
+    animated: bool,
+    // Cell, index in the cell and quad in the mesh of each sprite playing a clip,
+    // its four vertices are written again on a new frame.
+    quads: Vec<(Cell, usize, usize)>,
+    // Time of the map its clips were last stepped to, they are only stepped while visible.
+    time: Ms,
+}
+
+
+impl Chunk {
+    fn new(time: Ms) -> Chunk {
+        Chunk {
+            cells: BTreeMap::new(),
+            mesh: None,
+            bound: (::na::zero(), ::na::zero()),
+            dirty: true,
+            animated: false,
+            quads: Vec::new(),
+            time: time,
+        }
+    }
+
+    fn build(&mut self, display: &Display) {
+        use glium::index::PrimitiveType;
+
+        let _scope = profiler::scope("tile build");
+        self.dirty = false;
+        // Back to front: the higher a cell is on the screen, the earlier it is drawn.
+        let mut cells: Vec<(&Cell, &Vec<Sprite>)> = self.cells.iter()
+            .filter(|&(_, x)| !x.is_empty())
+            .collect();
+        cells.sort_by(|a, b| {
+            let (a, b) = (a.1[0].transform.position.y, b.1[0].transform.position.y);
+            b.partial_cmp(&a).unwrap()
+        });
+
+        // Indices are u16, checked before they could wrap.
+        let count = cells.iter().fold(0, |a, x| a + x.1.len());
+        assert!(count * 4 <= u16::max_value() as usize + 1,
+                "{} sprites do not fit in a chunk, make the chunks smaller", count);
+        let mut vertices: Vec<Vertex> = Vec::with_capacity(count * 4);
+        let mut ib: Vec<u16> = Vec::with_capacity(count * 6);
+        self.quads.clear();
+        for &(cell, sprites) in &cells {
+            for (i, sprite) in sprites.iter().enumerate() {
+                let num = (vertices.len() / 4) as u16;
+                if sprite.animating() {
+                    self.quads.push((*cell, i, num as usize));
+                }
+                vertices.extend(sprite.rectangle().as_array().iter().cloned());
+                ib.push(num * 4 + 0);
+                ib.push(num * 4 + 1);
+                ib.push(num * 4 + 2);
+                ib.push(num * 4 + 1);
+                ib.push(num * 4 + 3);
+                ib.push(num * 4 + 2);
+            }
+        }
+
+        if vertices.is_empty() {
+            self.mesh = None;
+            return;
+        }
+
+        let mut min = vertices[0].position;
+        let mut max = vertices[0].position;
+        for v in &vertices {
+            if v.position.x < min.x { min.x = v.position.x }
+            if v.position.y < min.y { min.y = v.position.y }
+            if v.position.x > max.x { max.x = v.position.x }
+            if v.position.y > max.y { max.y = v.position.y }
+        }
+        self.bound = (min, max);
+        self.mesh = Some(Mesh {
+            vertex_buffer: VertexBuffer::dynamic(display, &vertices[..]).unwrap(),
+            index_buffer: IndexBuffer::new(display,
+                                           PrimitiveType::TrianglesList,
+                                           &ib[..]).unwrap(),
+        });
+    }
+
+    // Steps the clips by `delta`, only the quads whose frame changed are written.
+    fn step(&mut self, renderer: &Renderer, delta: Ms, mut stream: EventStream) -> EventStream {
+        let mut frames = Vec::new();
+        for &(cell, i, quad) in &self.quads {
+            let sprite = &mut self.cells.get_mut(&cell).unwrap()[i];
+            let offset = sprite.texture_offset;
+            stream = sprite.update(renderer, delta, stream);
+            if sprite.texture_offset != offset {
+                frames.push((quad, sprite.rectangle().as_array()));
+            }
+        }
+        if frames.is_empty() { return stream }
+        if let Some(ref mut mesh) = self.mesh {
+            let mut map = mesh.vertex_buffer.map();
+            for (quad, vertices) in frames {
+                for k in 0..4 {
+                    map[quad * 4 + k] = vertices[k];
+                }
+            }
+        }
+        stream
+    }
+
+    fn center(&self) -> Vec2<f32> {
+        let (min, max) = self.bound;
+        (min + max) / 2.0
+    }
+
+    fn visible(&self, parent: &Mat) -> bool {
+        let (min, max) = self.bound;
+        let a = *parent * na![min.x, min.y, 0.0, 1.0];
+        let b = *parent * na![max.x, max.y, 0.0, 1.0];
+        a.x.max(b.x) >= -1.0 && a.x.min(b.x) <= 1.0 &&
+        a.y.max(b.y) >= -1.0 && a.y.min(b.y) <= 1.0
+    }
+}
+
+
+// Every chunk keeps its own vertex buffer, only built again when one of its
+// cells changed. A clip frame, if visible, writes the vertices of its sprite.
+pub struct TileMap {
+    texture: Rc<Texture>,
+    chunk_size: i32,
+    chunks: BTreeMap<Cell, Chunk>,
+    order: Vec<Cell>,
+    time: Ms,
+    // Matrix of the last draw, to know which chunks are visible.
+    view: Shared<Option<Mat>>,
+}
+
+
+impl TileMap {
+    pub fn new(texture: Rc<Texture>, chunk_size: i32) -> TileMap {
+        TileMap {
+            texture: texture,
+            chunk_size: chunk_size,
+            chunks: BTreeMap::new(),
+            order: Vec::new(),
+            time: 0,
+            view: Shared::new(None),
+        }
+    }
+
+    fn chunk_key(&self, cell: Cell) -> Cell {
+        let [x, y, z] = cell;
+        let n = self.chunk_size as f32;
+        [z, (x as f32 / n).floor() as i32, (y as f32 / n).floor() as i32]
+    }
+
+    // Clips are played from the time of the chunk, so that the sprites of a chunk
+    // started on frame boundaries all change frame together.
+    pub fn set(&mut self, cell: Cell, mut sprites: Vec<Sprite>) {
+        let key = self.chunk_key(cell);
+        let time = self.time;
+        let chunk = self.chunks.entry(key).or_insert_with(|| Chunk::new(time));
+        for sprite in sprites.iter_mut() {
+            assert!(sprite.texture == self.texture);
+            if let Some((clip, from)) = sprite.clip() {
+                sprite.play(clip, chunk.time + from);
+            }
+        }
+        chunk.dirty = true;
+        if sprites.is_empty() {
+            chunk.cells.remove(&cell);
+        }
+        else {
+            chunk.cells.insert(cell, sprites);
+        }
+        chunk.animated = chunk.cells.values()
+            .any(|x| x.iter().any(|sprite| sprite.animating()));
+    }
+
+    pub fn remove(&mut self, cell: Cell) {
+        self.set(cell, Vec::new());
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.order.clear();
+    }
+
+    // Every cell is removed, since its sprites use the old texture.
+    pub fn set_texture(&mut self, texture: Rc<Texture>) {
+        self.clear();
+        self.texture = texture;
+    }
+
+    fn sort(&mut self) {
+        let chunks = &self.chunks;
+        let mut order: Vec<Cell> = chunks.iter()
+            .filter(|&(_, chunk)| chunk.mesh.is_some())
+            .map(|(key, _)| *key)
+            .collect();
+        // Layers from bottom to top, then chunks from back to front.
+        order.sort_by(|a, b| {
+            use std::cmp::Ordering::Equal;
+
+            let (y_a, y_b) = (chunks[a].center().y, chunks[b].center().y);
+            match a[0].cmp(&b[0]) {
+                Equal => y_b.partial_cmp(&y_a).unwrap(),
+                x => x,
+            }
+        });
+        self.order = order;
+    }
+}
+
+
+impl Renderable for TileMap {
+    fn draw(&self, renderer: &Renderer, target: &mut Frame, parent: &Mat) {
+        self.view.set(Some (*parent));
+        let uniforms = uniform! {
+            matrix: *parent,
+            tex: &*self.texture,
+        };
+        for key in &self.order {
+            let chunk = &self.chunks[key];
+            if !chunk.visible(parent) { continue }
+            if let Some(ref mesh) = chunk.mesh {
+                renderer.draw(target, mesh, &uniforms);
+            }
+        }
+    }
+}
+
+
+impl Update for TileMap {
+    fn update(&mut self, renderer: &Renderer, delta: Ms, mut stream: EventStream)
+        -> EventStream
+    {
+        self.time += delta;
+        let time = self.time;
+        let view = self.view.get();
+        let mut changed = false;
+        for (_, chunk) in self.chunks.iter_mut() {
+            if chunk.dirty {
+                chunk.build(renderer.display);
+                changed = true;
+            }
+            if chunk.animated && view.map_or(false, |x| chunk.visible(&x)) {
+                // A chunk coming into view catches up in one step.
+                let delta = time - chunk.time;
+                chunk.time = time;
+                stream = chunk.step(renderer, delta, stream);
+            }
+        }
+        if changed { self.sort() }
+        return stream;
+    }
+}
@@ -1,12 +1,20 @@
-use std::path::PathBuf;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use freetype as ft;
 use nalgebra::Vec2;
 use unicode_normalization::UnicodeNormalization;
+use glium::Display;
 use color::Color;
 use canvas::Canvas;
+use resources::{Resource, ResourceError};
+
+
+// DejaVu Sans Mono, see font/LICENSE.
+static BUILTIN_FONT: &'static [u8] = include_bytes!("font/DejaVuSansMono.ttf");
 
 
 macro_rules! cast (
@@ -49,6 +57,15 @@ impl GlyphCache {
     pub fn new() -> GlyphCache {
         GlyphCache(Arc::new(Mutex::new(HashMap::new())))
     }
+
+    // Drops the glyphs of `font`, after it was loaded again.
+    pub fn forget(&self, font: &Path) {
+        let mut cache = self.lock().unwrap();
+        let keys: Vec<Key> = cache.keys().filter(|x| &*x.font_path == font).cloned().collect();
+        for key in keys {
+            cache.remove(&key);
+        }
+    }
 }
 
 
@@ -73,8 +90,11 @@ pub struct Face<'a> {
 
 
 impl<'a> Face<'a> {
-    pub fn new(system: &'a System, font: PathBuf) -> Face<'a> {
-        let face = system.library.new_face(font, 0).unwrap();
+    pub fn new(system: &'a System, style: &'a TextStyle) -> Face<'a> {
+        let face = match style.font_data {
+            Some (ref data) => system.library.new_memory_face(&data[..], 0).unwrap(),
+            None => system.library.new_face(style.font.clone(), 0).unwrap(),
+        };
         Face {
             load_flag: ft::face::RENDER,
             ft_face: face,
@@ -134,7 +154,9 @@ impl Glyph {
 
 #[derive(Clone)]
 pub struct TextStyle {
+    // Names the font in the glyph cache when it is read from `font_data`.
     pub font: PathBuf,
+    pub font_data: Option<Arc<Vec<u8>>>,
     pub color: Color,
     pub font_size: u32,
     pub underline: bool,
@@ -150,6 +172,7 @@ impl TextStyle {
     pub fn new(font: PathBuf) -> TextStyle {
         TextStyle {
             font: font,
+            font_data: None,
             color: Color::black(),
             font_size: 18,
             underline: false,
@@ -161,6 +184,22 @@ impl TextStyle {
         }
     }
 
+    // A font which is not a file, `name` has to tell it apart from the others.
+    pub fn from_bytes(name: &str, data: Vec<u8>) -> TextStyle {
+        TextStyle { font_data: Some (Arc::new(data)), ..TextStyle::new(PathBuf::from(name)) }
+    }
+
+    pub fn from_reader<R: Read>(name: &str, mut reader: R) -> io::Result<TextStyle> {
+        let mut data = Vec::new();
+        try!(reader.read_to_end(&mut data));
+        Ok (TextStyle::from_bytes(name, data))
+    }
+
+    // Shipped within the engine, for when the game has no font of its own.
+    pub fn builtin() -> TextStyle {
+        TextStyle::from_bytes("<builtin>", BUILTIN_FONT.to_vec())
+    }
+
     pub fn factor(self, hidpi_factor: f32) -> TextStyle {
         macro_rules! scale (($x:expr) => (
             $x as f32 * hidpi_factor
@@ -171,6 +210,7 @@ impl TextStyle {
 
         TextStyle {
             font: self.font,
+            font_data: self.font_data,
             color: self.color,
             font_size: scale!(self.font_size) as u32,
             underline: self.underline,
@@ -191,6 +231,7 @@ impl TextStyle {
             width: self.width.map(|x| scale!(x) as usize),
             height: self.height.map(|x| scale!(x) as usize),
             font: self.font,
+            font_data: self.font_data,
             color: self.color,
             font_size: scale!(self.font_size) as u32,
             underline: self.underline,
@@ -207,7 +248,7 @@ pub fn load(cache: &mut CacheMap, style: &TextStyle, text: &String)
     -> Vec<(char, Arc<Glyph>)>
 {
     let system = System::new();
-    let face = Face::new(&system, style.font.clone());
+    let face = Face::new(&system, style);
     face.set_size(style.font_size);
     let mut result = Vec::with_capacity(text.nfc().count());
     for c in text.nfc() {
@@ -312,3 +353,20 @@ pub fn draw(style: &TextStyle, glyphs: Vec<(char, &Glyph)>)
 
     return canvas.factor(style.hidpi_factor);
 }
+
+
+// A font file read by a `Manager`, named by its path in the glyph cache,
+// see `GlyphCache::forget` when it is reloaded.
+impl Resource for TextStyle {
+    fn load(_: &Display, path: &Path, data: Vec<u8>) -> Result<TextStyle, ResourceError> {
+        let valid = {
+            let system = System::new();
+            let face = system.library.new_memory_face(&data[..], 0);
+            face.is_ok()
+        };
+        if !valid {
+            return Err (ResourceError::Decode(path.to_path_buf(), "not a font".to_string()));
+        }
+        Ok (TextStyle::from_bytes(&path.to_string_lossy(), data))
+    }
+}
@@ -1,29 +1,204 @@
 use image;
 use id::Id;
-use glium::Display;
-use glium::texture::{Texture2dDataSource, CompressedTexture2d};
+use glium::{Display, Rect};
+use glium::texture::{Texture2dDataSource, CompressedTexture2d, Texture2d, RawImage2d,
+                     MipmapsOption, CompressedMipmapsOption};
+use glium::uniforms::{AsUniformValue, UniformValue, SamplerBehavior, SamplerWrapFunction,
+                      MinifySamplerFilter, MagnifySamplerFilter};
+use std::borrow::Cow;
 use std::cmp::{PartialEq, Eq};
 use std::path::Path;
-use resources::Resource;
+use color::Color;
+use resources::{Resource, AsyncResource, ResourceError, utf8};
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Wrap {
+    Clamp,
+    Repeat,
+    Mirror,
+}
+
+
+// How a texture is stored and sampled. Storage and mipmaps are set when
+// the texture is created, filtering and wrapping every time it is drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TextureOptions {
+    pub compressed: bool,
+    pub filter: Filter,
+    pub wrap: Wrap,
+    pub mipmaps: bool,
+}
+
+
+impl TextureOptions {
+    pub fn new() -> TextureOptions {
+        TextureOptions { compressed: true, filter: Filter::Linear, wrap: Wrap::Clamp, mipmaps: true }
+    }
+
+    // Uncompressed and sharp, for pixel art.
+    pub fn pixel() -> TextureOptions {
+        TextureOptions { compressed: false, filter: Filter::Nearest, ..TextureOptions::new() }
+    }
+
+    // The metadata of a texture, one `<option> <value>` per line:
+    // `storage compressed|plain`, `filter nearest|linear`,
+    // `wrap clamp|repeat|mirror`, `mipmaps on|off`.
+    pub fn parse(text: &str) -> Result<TextureOptions, String> {
+        let mut options = TextureOptions::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") { continue }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match &words[..] {
+                &["storage", "compressed"] => options.compressed = true,
+                &["storage", "plain"] => options.compressed = false,
+                &["filter", "nearest"] => options.filter = Filter::Nearest,
+                &["filter", "linear"] => options.filter = Filter::Linear,
+                &["wrap", "clamp"] => options.wrap = Wrap::Clamp,
+                &["wrap", "repeat"] => options.wrap = Wrap::Repeat,
+                &["wrap", "mirror"] => options.wrap = Wrap::Mirror,
+                &["mipmaps", "on"] => options.mipmaps = true,
+                &["mipmaps", "off"] => options.mipmaps = false,
+                _ => return Err (format!("unknown option: {}", line)),
+            }
+        }
+        Ok (options)
+    }
+
+    fn sampler(&self) -> SamplerBehavior {
+        let wrap = match self.wrap {
+            Wrap::Clamp => SamplerWrapFunction::Clamp,
+            Wrap::Repeat => SamplerWrapFunction::Repeat,
+            Wrap::Mirror => SamplerWrapFunction::Mirror,
+        };
+        let (minify, magnify) = match (self.filter, self.mipmaps) {
+            (Filter::Nearest, false) => (MinifySamplerFilter::Nearest, MagnifySamplerFilter::Nearest),
+            (Filter::Nearest, true) => (MinifySamplerFilter::NearestMipmapNearest, MagnifySamplerFilter::Nearest),
+            (Filter::Linear, false) => (MinifySamplerFilter::Linear, MagnifySamplerFilter::Linear),
+            (Filter::Linear, true) => (MinifySamplerFilter::LinearMipmapLinear, MagnifySamplerFilter::Linear),
+        };
+        SamplerBehavior {
+            wrap_function: (wrap, wrap, wrap),
+            minify_filter: minify,
+            magnify_filter: magnify,
+            ..Default::default()
+        }
+    }
+}
+
+
+pub enum TextureData {
+    Compressed(CompressedTexture2d),
+    // Can be written to after its creation, see `Texture::write`.
+    Plain(Texture2d),
+}
+
 
 pub struct Texture {
     pub id: Id,
     pub height: u32,
     pub width: u32,
-    pub data: CompressedTexture2d,
+    pub data: TextureData,
+    pub options: TextureOptions,
+}
+
+
+// As a `tex` uniform, sampled as its options say.
+impl<'a> AsUniformValue for &'a Texture {
+    fn as_uniform_value(&self) -> UniformValue {
+        let sampler = Some (self.options.sampler());
+        match self.data {
+            TextureData::Compressed(ref x) => UniformValue::CompressedTexture2d(x, sampler),
+            TextureData::Plain(ref x) => UniformValue::Texture2d(x, sampler),
+        }
+    }
 }
 
 
 impl Texture {
     pub fn new<'a, T>(display: &Display, source: T) -> Texture
             where T: Texture2dDataSource<'a> {
-        let tex = CompressedTexture2d::new(display, source).unwrap();
-        Texture {
-            id: Id::new(),
-            width: tex.get_width(),
-            height: tex.get_height().unwrap(),
-            data: tex,
+        Texture::try_new(display, source).unwrap()
+    }
+
+    pub fn try_new<'a, T>(display: &Display, source: T) -> Result<Texture, String>
+            where T: Texture2dDataSource<'a> {
+        Texture::with_options(display, source, TextureOptions::new())
+    }
+
+    pub fn with_options<'a, T>(display: &Display, source: T, options: TextureOptions)
+        -> Result<Texture, String>
+            where T: Texture2dDataSource<'a>
+    {
+        let data = if options.compressed {
+            let mipmaps = if options.mipmaps { CompressedMipmapsOption::AutoGeneratedMipmaps }
+                          else { CompressedMipmapsOption::NoMipmap };
+            match CompressedTexture2d::with_mipmaps(display, source, mipmaps) {
+                Ok (x) => TextureData::Compressed(x),
+                Err (e) => return Err (format!("{:?}", e)),
+            }
         }
+        else {
+            let mipmaps = if options.mipmaps { MipmapsOption::AutoGeneratedMipmaps }
+                          else { MipmapsOption::NoMipmap };
+            match Texture2d::with_mipmaps(display, source, mipmaps) {
+                Ok (x) => TextureData::Plain(x),
+                Err (e) => return Err (format!("{:?}", e)),
+            }
+        };
+        let (width, height) = match data {
+            TextureData::Compressed(ref x) => (x.get_width(), x.get_height().unwrap()),
+            TextureData::Plain(ref x) => (x.get_width(), x.get_height().unwrap()),
+        };
+        Ok (Texture { id: Id::new(), width: width, height: height, data: data, options: options })
+    }
+
+    // Transparent, to be filled with `write`. Without mipmaps, they would not follow the writes.
+    pub fn empty(display: &Display, width: u32, height: u32) -> Result<Texture, String> {
+        let pixels = vec![Color::new(0.0, 0.0, 0.0, 0.0); (width * height) as usize];
+        let options = TextureOptions { compressed: false, mipmaps: false, ..TextureOptions::new() };
+        Texture::with_options(display, RawImage2d {
+            data: Cow::Owned(pixels),
+            width: width,
+            height: height,
+            format: Color::get_format(),
+        }, options)
+    }
+
+    // Pixels in the order of a `Canvas` buffer, bottom row first,
+    // `(x, y)` the top left corner counted from the top like `Sprite::offset`.
+    pub fn write(&self, (x, y): (u32, u32), (width, height): (u32, u32), pixels: Vec<Color>) {
+        let texture = match self.data {
+            TextureData::Plain(ref x) => x,
+            TextureData::Compressed(_) => panic!("a compressed texture cannot be written"),
+        };
+        let rect = Rect { left: x, bottom: self.height - y - height, width: width, height: height };
+        texture.write(rect, RawImage2d {
+            data: Cow::Owned(pixels),
+            width: width,
+            height: height,
+            format: Color::get_format(),
+        });
+    }
+
+    // Magenta and black squares, stands for a texture that failed to load.
+    pub fn checkerboard(display: &Display) -> Texture {
+        const SIZE: u32 = 256;
+        const SQUARE: u32 = 32;
+
+        let image = image::ImageBuffer::from_fn(SIZE, SIZE, |x, y| {
+            if (x / SQUARE + y / SQUARE) % 2 == 0 { image::Rgba([255u8, 0, 255, 255]) }
+            else { image::Rgba([0u8, 0, 0, 255]) }
+        });
+        Texture::new(display, image)
     }
 }
 
@@ -39,7 +214,91 @@ impl Eq for Texture {}
 
 
 impl Resource for Texture {
-    fn load(display: &Display, path: &Path) -> Texture {
-        Texture::new(display, image::open(path).unwrap())
+    fn load(display: &Display, path: &Path, data: Vec<u8>) -> Result<Texture, ResourceError> {
+        let image = try!(Texture::decode(path, data));
+        Texture::upload(display, path, image)
+    }
+
+    fn load_meta(display: &Display, path: &Path, data: Vec<u8>, meta: Vec<u8>)
+        -> Result<Texture, ResourceError>
+    {
+        let image = try!(Texture::decode_meta(path, data, meta));
+        Texture::upload(display, path, image)
+    }
+}
+
+
+impl AsyncResource for Texture {
+    type Data = (image::DynamicImage, TextureOptions);
+
+    fn decode(path: &Path, data: Vec<u8>) -> Result<Self::Data, ResourceError> {
+        image::load_from_memory(&data)
+            .map(|x| (x, TextureOptions::new()))
+            .map_err(|e| ResourceError::Decode(path.to_path_buf(), e.to_string()))
+    }
+
+    fn decode_meta(path: &Path, data: Vec<u8>, meta: Vec<u8>) -> Result<Self::Data, ResourceError> {
+        let options = try!(TextureOptions::parse(&try!(utf8(path, meta)))
+            .map_err(|e| ResourceError::Decode(path.to_path_buf(), e)));
+        let (image, _) = try!(Texture::decode(path, data));
+        Ok ((image, options))
+    }
+
+    fn upload(display: &Display, path: &Path, (image, options): Self::Data)
+        -> Result<Texture, ResourceError>
+    {
+        Texture::with_options(display, image, options)
+            .map_err(|e| ResourceError::Upload(path.to_path_buf(), e))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::Path;
+    use image::GenericImage;
+    use resources::{AsyncResource, decode_bytes, decode_reader};
+    use super::{Texture, TextureOptions, Filter, Wrap};
+
+    const BLOCK: &'static [u8] = include_bytes!("../../assets/tile/block.png");
+    const META: &'static str = include_str!("../../assets/tile/block.png.meta");
+
+    #[test]
+    fn parse() {
+        let options = TextureOptions::parse(META).unwrap();
+        assert_eq!(options, TextureOptions::pixel());
+        let options = TextureOptions::parse("wrap mirror\nmipmaps off").unwrap();
+        assert_eq!(options.wrap, Wrap::Mirror);
+        assert_eq!(options.filter, Filter::Linear);
+        assert!(!options.mipmaps);
+        assert_eq!(TextureOptions::parse("").unwrap(), TextureOptions::new());
+        for text in ["filter cubic", "storage", "wrap clamp repeat"].iter() {
+            assert!(TextureOptions::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn from_bytes() {
+        let (image, options) = decode_bytes::<Texture>("block.png", BLOCK).unwrap();
+        assert_eq!(image.dimensions(), (1024, 1024));
+        assert_eq!(options, TextureOptions::new());
+        assert!(decode_bytes::<Texture>("block.png", b"not a png").is_err());
+    }
+
+    #[test]
+    fn from_reader() {
+        let (image, _) = decode_reader::<Texture, _>("block.png", Cursor::new(BLOCK)).unwrap();
+        assert_eq!(image.dimensions(), (1024, 1024));
+    }
+
+    #[test]
+    fn with_meta() {
+        let path = Path::new("block.png");
+        let (_, options) = Texture::decode_meta(path, BLOCK.to_vec(), META.as_bytes().to_vec()).unwrap();
+        assert_eq!(options, TextureOptions::parse(META).unwrap());
+        assert!(Texture::decode_meta(path, BLOCK.to_vec(), b"filter cubic".to_vec()).is_err());
+        let (_, options) = Texture::decode(path, BLOCK.to_vec()).unwrap();
+        assert_eq!(options, TextureOptions::new());
     }
 }
@@ -0,0 +1,327 @@
+use std::path::Path;
+use na::Vec2;
+use glium::Display;
+use color::Color;
+use easing::Easing;
+use timer::Ms;
+use resources::{Resource, AsyncResource, ResourceError, utf8};
+use animation::{State, List, tween, delay, series, parallel};
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Value {
+    Scalar(f32),
+    Vector(Vec2<f32>),
+    Color(Color),
+}
+
+
+impl Value {
+    fn same_kind(&self, other: &Value) -> bool {
+        match (*self, *other) {
+            (Value::Scalar(_), Value::Scalar(_)) |
+            (Value::Vector(_), Value::Vector(_)) |
+            (Value::Color(_), Value::Color(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+
+// Where a property lives in `T`, see `Animatable`.
+pub enum Lens<T> {
+    Scalar(fn(&mut T) -> &mut f32),
+    Vector(fn(&mut T) -> &mut Vec2<f32>),
+    Color(fn(&mut T) -> &mut Color),
+}
+
+
+pub trait Animatable: Sized {
+    fn lens(property: &str) -> Option<Lens<Self>>;
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Loop {
+    Once,
+    Repeat,
+    PingPong,
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Keyframe {
+    pub time: Ms,
+    pub value: Value,
+    // How the value gets here from the previous keyframe.
+    pub easing: Easing,
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub property: String,
+    pub keyframes: Vec<Keyframe>,
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Timeline {
+    pub mode: Loop,
+    pub tracks: Vec<Track>,
+}
+
+
+fn parse_keyframe(line: &str) -> Result<Keyframe, String> {
+    let mut words = line.split_whitespace();
+    let time = match words.next().map(|x| x.parse::<Ms>()) {
+        Some (Ok (x)) => x,
+        _ => return Err (format!("bad keyframe time: {}", line)),
+    };
+    let mut numbers = Vec::new();
+    let mut easing = Easing::Linear;
+    for word in words {
+        match word.parse::<f32>() {
+            Ok (x) => numbers.push(x),
+            Err (_) => {
+                easing = try!(word.parse());
+                break;
+            }
+        }
+    }
+    let value = match &numbers[..] {
+        &[x] => Value::Scalar(x),
+        &[x, y] => Value::Vector(na![x, y]),
+        &[r, g, b, a] => Value::Color(Color::new(r, g, b, a)),
+        _ => return Err (format!("a value has 1, 2 or 4 numbers: {}", line)),
+    };
+    Ok (Keyframe { time: time, value: value, easing: easing })
+}
+
+
+impl Timeline {
+    // One directive per line: `loop once|repeat|pingpong`, `track <property>`,
+    // or a keyframe `<time ms> <numbers> [easing]` of the last track.
+    pub fn parse(text: &str) -> Result<Timeline, String> {
+        let mut timeline = Timeline { mode: Loop::Once, tracks: Vec::new() };
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") { continue }
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some ("loop") => {
+                    timeline.mode = match words.next() {
+                        Some ("once") => Loop::Once,
+                        Some ("repeat") => Loop::Repeat,
+                        Some ("pingpong") => Loop::PingPong,
+                        _ => return Err (format!("unknown loop mode: {}", line)),
+                    }
+                }
+                Some ("track") => {
+                    let property = match words.next() {
+                        Some (x) => x.to_string(),
+                        None => return Err ("track without property".to_string()),
+                    };
+                    timeline.tracks.push(Track { property: property, keyframes: Vec::new() });
+                }
+                _ => {
+                    let keyframe = try!(parse_keyframe(line));
+                    match timeline.tracks.last_mut() {
+                        Some (track) => track.keyframes.push(keyframe),
+                        None => return Err (format!("keyframe outside a track: {}", line)),
+                    }
+                }
+            }
+        }
+        for track in &timeline.tracks {
+            try!(track.check());
+        }
+        Ok (timeline)
+    }
+
+    pub fn duration(&self) -> Ms {
+        self.tracks.iter().map(|x| x.duration()).max().unwrap_or(0)
+    }
+
+    pub fn compile<T: Animatable>(&self) -> Result<State<T>, String> {
+        let duration = self.duration();
+        let mut forward = Vec::with_capacity(self.tracks.len());
+        for track in &self.tracks {
+            forward.push(try!(track.compile(duration)));
+        }
+        let state = match self.mode {
+            Loop::Once => parallel(forward),
+            Loop::Repeat => {
+                let once = parallel(forward);
+                State::Repeat(None, box once.clone(), box once)
+            }
+            Loop::PingPong => {
+                let mut backward = Vec::with_capacity(self.tracks.len());
+                for track in &self.tracks {
+                    backward.push(try!(track.reverse(duration).compile(duration)));
+                }
+                let once = series(vec![parallel(forward), parallel(backward)]);
+                State::Repeat(None, box once.clone(), box once)
+            }
+        };
+        Ok (state)
+    }
+}
+
+
+impl Track {
+    fn check(&self) -> Result<(), String> {
+        let first = match self.keyframes.first() {
+            Some (x) => x,
+            None => return Err (format!("track {} has no keyframe", self.property)),
+        };
+        let mut time = 0;
+        for keyframe in &self.keyframes {
+            if keyframe.time < time {
+                return Err (format!("keyframes of {} are out of order", self.property));
+            }
+            if !keyframe.value.same_kind(&first.value) {
+                return Err (format!("keyframes of {} mix value kinds", self.property));
+            }
+            time = keyframe.time;
+        }
+        Ok (())
+    }
+
+    fn duration(&self) -> Ms {
+        self.keyframes.last().map_or(0, |x| x.time)
+    }
+
+    // Mirrored in time, each segment is eased by its mirrored curve.
+    fn reverse(&self, duration: Ms) -> Track {
+        let keys = &self.keyframes;
+        let mut keyframes = Vec::with_capacity(keys.len());
+        for i in (0..keys.len()).rev() {
+            keyframes.push(Keyframe {
+                time: duration - keys[i].time,
+                value: keys[i].value,
+                easing: if i + 1 < keys.len() { keys[i+1].easing.mirrored() } else { Easing::Linear },
+            });
+        }
+        Track { property: self.property.clone(), keyframes: keyframes }
+    }
+
+    // Padded to `duration` so that every track of a timeline ends together.
+    fn compile<T: Animatable>(&self, duration: Ms) -> Result<State<T>, String> {
+        let lens = match T::lens(&self.property) {
+            Some (x) => x,
+            None => return Err (format!("unknown property: {}", self.property)),
+        };
+        let mut list: List<T> = Vec::with_capacity(self.keyframes.len() + 2);
+        let mut last = &self.keyframes[0];
+        list.push(delay(last.time));
+        for keyframe in &self.keyframes {
+            let ms = keyframe.time - last.time;
+            let easing = keyframe.easing;
+            list.push(match (&lens, last.value, keyframe.value) {
+                (&Lens::Scalar(f), Value::Scalar(a), Value::Scalar(b)) =>
+                    tween(ms, f, a, b, easing),
+                (&Lens::Vector(f), Value::Vector(a), Value::Vector(b)) =>
+                    tween(ms, f, a, b, easing),
+                (&Lens::Color(f), Value::Color(a), Value::Color(b)) =>
+                    tween(ms, f, a, b, easing),
+                _ => return Err (format!("wrong value kind for {}", self.property)),
+            });
+            last = keyframe;
+        }
+        list.push(delay(duration - last.time));
+        Ok (series(list))
+    }
+}
+
+
+impl Resource for Timeline {
+    fn load(_: &Display, path: &Path, data: Vec<u8>) -> Result<Timeline, ResourceError> {
+        Timeline::decode(path, data)
+    }
+}
+
+
+// Nothing to upload, all of it is parsed on the worker.
+impl AsyncResource for Timeline {
+    type Data = Timeline;
+
+    fn decode(path: &Path, data: Vec<u8>) -> Result<Timeline, ResourceError> {
+        let text = try!(utf8(path, data));
+        Timeline::parse(&text).map_err(|e| ResourceError::Decode(path.to_path_buf(), e))
+    }
+
+    fn upload(_: &Display, _: &Path, x: Timeline) -> Result<Timeline, ResourceError> {
+        Ok (x)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read};
+    use color::Color;
+    use sprite::Sprite;
+    use easing::{Easing, Mode};
+    use resources::{ResourceError, decode_bytes, decode_reader};
+    use super::{Timeline, Track, Keyframe, Loop, Value};
+
+    const HIT: &'static str = include_str!("../../assets/anim/hit.anim");
+
+    struct Broken;
+
+    impl Read for Broken {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err (io::Error::new(io::ErrorKind::Other, "broken"))
+        }
+    }
+
+    #[test]
+    fn from_bytes() {
+        let timeline = decode_bytes::<Timeline>("hit.anim", HIT.as_bytes()).unwrap();
+        assert_eq!(timeline.mode, Loop::Once);
+        assert_eq!(timeline.tracks.len(), 1);
+        assert_eq!(timeline.tracks[0].property, "tint");
+        assert_eq!(timeline.duration(), 200);
+        assert_eq!(timeline.tracks[0].keyframes[1].value, Value::Color(Color::new(1.0, 0.3, 0.3, 1.0)));
+        assert!(timeline.compile::<Sprite>().is_ok());
+    }
+
+    #[test]
+    fn from_reader() {
+        let timeline = decode_reader::<Timeline, _>("hit.anim", Cursor::new(HIT)).unwrap();
+        assert_eq!(timeline.duration(), 200);
+        match decode_reader::<Timeline, _>("broken.anim", Broken) {
+            Err (ResourceError::Decode(..)) => {}
+            _ => panic!("a failed read is a decode error"),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let bad = ["loop sometimes", "0 1.0", "track alpha\n10 1.0\n0 0.0",
+                   "track alpha\n0 1.0\n10 1.0 2.0", "track alpha\n0 1.0 2.0 3.0"];
+        for text in bad.iter() {
+            assert!(decode_bytes::<Timeline>("bad.anim", text.as_bytes()).is_err(), "{}", text);
+        }
+        let timeline = Timeline::parse("track nothing\n0 1.0").unwrap();
+        assert!(timeline.compile::<Sprite>().is_err());
+    }
+
+    #[test]
+    fn reverse() {
+        let key = |time, value, easing| Keyframe { time: time, value: Value::Scalar(value), easing: easing };
+        let track = Track {
+            property: "alpha".to_string(),
+            keyframes: vec![key(0, 0.0, Easing::Linear),
+                            key(100, 1.0, Easing::Quad(Mode::In)),
+                            key(300, 0.5, Easing::Sine(Mode::InOut))],
+        };
+        let reversed = track.reverse(400);
+        let times: Vec<_> = reversed.keyframes.iter().map(|x| x.time).collect();
+        assert_eq!(times, vec![100, 300, 400]);
+        assert_eq!(reversed.keyframes[0].value, Value::Scalar(0.5));
+        assert_eq!(reversed.keyframes[1].easing, Easing::Sine(Mode::InOut));
+        assert_eq!(reversed.keyframes[2].easing, Easing::Quad(Mode::Out));
+    }
+}
@@ -1,4 +1,6 @@
 use time;
+use std::rc::Rc;
+use std::cell::Cell;
 use std::collections::VecDeque;
 
 pub type Ns = u64;
@@ -6,27 +8,105 @@ pub type Ms = u64;
 
 const SECOND_NS: Ns = 1_000_000_000;
 const MS_NS: Ns = SECOND_NS / 1000;
+const MAX_STEPS: u64 = 8;
 
-pub fn now() -> Ms {
-    time::precise_time_ns() / (SECOND_NS/1000)
+
+// Where the time comes from, so that timing code can run without waiting.
+pub trait Clock {
+    fn now_ns(&self) -> Ns;
+    fn sleep(&self, ns: Ns);
+}
+
+
+pub struct RealClock;
+
+
+impl Clock for RealClock {
+    fn now_ns(&self) -> Ns {
+        time::precise_time_ns()
+    }
+
+    fn sleep(&self, ns: Ns) {
+        ::std::thread::sleep_ms((ns / MS_NS) as u32);
+    }
+}
+
+
+// Only moves when told to, clones share the same time.
+#[derive(Clone)]
+pub struct ManualClock {
+    now: Rc<Cell<Ns>>,
+}
+
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock { now: Rc::new(Cell::new(0)) }
+    }
+
+    pub fn advance(&self, ns: Ns) {
+        self.now.set(self.now.get() + ns);
+    }
+
+    pub fn advance_ms(&self, ms: Ms) {
+        self.advance(ms * MS_NS);
+    }
+}
+
+
+impl Clock for ManualClock {
+    fn now_ns(&self) -> Ns {
+        self.now.get()
+    }
+
+    // Sleeping is just time passing.
+    fn sleep(&self, ns: Ns) {
+        self.advance(ns);
+    }
 }
 
 
+// Keeps two clocks: the real one follows the wall clock,
+// the game one runs `scale` times as fast and stops while paused.
+// Game time is also handed out in fixed steps, see `tick`.
 pub struct ProgramTimer {
+    clock: Box<Clock>,
     updates: VecDeque<Ns>,
     now: Ns,
+    game: Ns,
+    step: Ns,
+    ticks: u64,
+    // Game time not stepped yet, in ns so that no fraction of a ms is lost.
+    accumulator: Ns,
+    // Whole ms of the last frame, for what runs once a frame. Tweens and
+    // the camera run in the steps of `tick`, which lose no fraction of a ms.
     pub delta: Ms,
+    pub real_delta: Ms,
+    scale: f64,
+    paused: bool,
     frame_limit: u64,
 }
 
 
 impl ProgramTimer {
     pub fn new() -> ProgramTimer {
-        let now = time::precise_time_ns();
+        ProgramTimer::with_clock(box RealClock)
+    }
+
+    pub fn with_clock(clock: Box<Clock>) -> ProgramTimer {
+        let now = clock.now_ns();
         ProgramTimer {
+            clock: clock,
             updates: VecDeque::with_capacity(128),
             now: now,
+            game: 0,
+            step: SECOND_NS / 60,
+            ticks: 0,
+            accumulator: 0,
             delta: 0,
+            real_delta: 0,
+            scale: 1.0,
+            paused: false,
             frame_limit: 60,
         }
     }
@@ -35,31 +115,103 @@ impl ProgramTimer {
         ProgramTimer { frame_limit: limit, ..self }
     }
 
+    // Simulation steps per second of game time.
+    pub fn fixed_step(self, rate: u64) -> ProgramTimer {
+        ProgramTimer { step: SECOND_NS / rate, ..self }
+    }
+
     // Thanks https://github.com/PistonDevelopers/fps_counter/
     pub fn update(&mut self) {
-        let now = time::precise_time_ns();
+        let now = self.clock.now_ns();
         let per_frame_ns = SECOND_NS / self.frame_limit;
         let delta = now - self.now;
         if per_frame_ns > delta {
-            ::std::thread::sleep_ms(((per_frame_ns - delta) / MS_NS) as u32);
+            self.clock.sleep(per_frame_ns - delta);
         }
-        let now = time::precise_time_ns();
-        let a_second_ago = now - SECOND_NS;
-        self.delta = (now - self.now) / MS_NS; // microsecond
+        let now = self.clock.now_ns();
+        let a_second_ago = now.saturating_sub(SECOND_NS);
+        let real = now - self.now;
+        let game = if self.paused { 0 } else { (real as f64 * self.scale) as Ns };
+        // Both in whole ms of the clock, so rounding never accumulates.
+        self.real_delta = now / MS_NS - self.now / MS_NS;
+        self.delta = (self.game + game) / MS_NS - self.game / MS_NS;
         self.now = now;
+        self.game += game;
+        // After a long stall, drop the backlog rather than catching up at once.
+        self.accumulator = ::std::cmp::min(self.accumulator + game, self.step * MAX_STEPS);
         while self.updates.front().map_or(false, |t| *t < a_second_ago) {
             self.updates.pop_front();
         }
         self.updates.push_back(now);
     }
 
+    pub fn sleep(&self, ms: Ms) {
+        self.clock.sleep(ms * MS_NS);
+    }
+
     pub fn fps(&self) -> usize {
         self.updates.len()
     }
 
+    // Game time, since the timer was created.
     pub fn now(&self) -> Ms {
+        self.game / MS_NS
+    }
+
+    pub fn now_ns(&self) -> Ns {
+        self.game
+    }
+
+    // Takes one fixed step from the game time accumulated by `update`,
+    // call it until it returns `None` and advance the simulation by each delta.
+    // Steps are counted in ns: their ms deltas add up to the exact game time,
+    // a 60 Hz step lasts 16 or 17 ms, never 0.
+    pub fn tick(&mut self) -> Option<Ms> {
+        if self.accumulator < self.step { return None }
+        self.accumulator -= self.step;
+        self.ticks += 1;
+        Some ((self.ticks * self.step) / MS_NS - ((self.ticks - 1) * self.step) / MS_NS)
+    }
+
+    // How far rendering is between the last step and the next one, in [0, 1).
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator as f64 / self.step as f64) as f32
+    }
+
+    pub fn step_ns(&self) -> Ns {
+        self.step
+    }
+
+    // For simulation code wanting the exact step, 1/60 s is not a whole number of ms.
+    pub fn step_secs(&self) -> f64 {
+        self.step as f64 / SECOND_NS as f64
+    }
+
+    pub fn real_now(&self) -> Ms {
         self.now / MS_NS
     }
+
+    pub fn scale(&self) -> f32 {
+        self.scale as f32
+    }
+
+    // 0.25 is a slow-motion, 2.0 a fast-forward.
+    pub fn set_scale(&mut self, scale: f32) {
+        assert!(scale >= 0.0);
+        self.scale = scale as f64;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
 }
 
 
@@ -93,3 +245,104 @@ impl Timer {
         self.now += delta;
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::{ProgramTimer, ManualClock, Clock};
+
+    fn timer() -> (ManualClock, ProgramTimer) {
+        let clock = ManualClock::new();
+        (clock.clone(), ProgramTimer::with_clock(box clock))
+    }
+
+    #[test]
+    fn update_follows_the_clock() {
+        let (clock, mut timer) = timer();
+        clock.advance_ms(20);
+        timer.update();
+        assert_eq!(timer.delta, 20);
+        assert_eq!(timer.real_delta, 20);
+        assert_eq!(timer.now(), 20);
+        assert_eq!(clock.now_ns(), 20_000_000);
+    }
+
+    #[test]
+    fn update_waits_for_the_frame_limit() {
+        let (clock, mut timer) = timer();
+        clock.advance_ms(5);
+        timer.update();
+        // 60 fps, the clock is moved to the end of the frame instead of sleeping.
+        assert_eq!(clock.now_ns(), 1_000_000_000 / 60);
+        assert_eq!(timer.real_delta, 16);
+    }
+
+    #[test]
+    fn fixed_steps() {
+        let (clock, timer) = timer();
+        let mut timer = timer.fixed_step(100);
+        clock.advance_ms(35);
+        timer.update();
+        assert_eq!(timer.now_ns(), 35_000_000);
+        let mut steps = Vec::new();
+        while let Some(x) = timer.tick() { steps.push(x) }
+        assert_eq!(steps, vec![10, 10, 10]);
+        assert!((timer.alpha() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fractions_of_a_ms_are_kept() {
+        let (clock, mut timer) = timer();
+        let mut total = 0;
+        for _ in 0..6 {
+            clock.advance(16_666_667);
+            timer.update();
+            while let Some(x) = timer.tick() { total += x }
+        }
+        // The ms of six 60 Hz steps add up to their ns, about 100, not 6 * 16.
+        assert_eq!(total, 6 * timer.step_ns() / 1_000_000);
+        assert!(total > 6 * 16);
+        assert_eq!(timer.now_ns(), 6 * 16_666_667);
+    }
+
+    #[test]
+    fn steps_are_capped_after_a_stall() {
+        let (clock, timer) = timer();
+        let mut timer = timer.fixed_step(100);
+        clock.advance_ms(1000);
+        timer.update();
+        let mut count = 0;
+        while let Some(_) = timer.tick() { count += 1 }
+        assert_eq!(count, super::MAX_STEPS);
+    }
+
+    #[test]
+    fn pause_and_scale() {
+        let (clock, mut timer) = timer();
+        timer.pause();
+        clock.advance_ms(40);
+        timer.update();
+        assert_eq!(timer.delta, 0);
+        assert_eq!(timer.real_delta, 40);
+        assert_eq!(timer.tick(), None);
+        timer.resume();
+        timer.set_scale(0.5);
+        clock.advance_ms(40);
+        timer.update();
+        assert_eq!(timer.delta, 20);
+        assert_eq!(timer.now(), 20);
+        assert_eq!(timer.real_now(), 80);
+    }
+
+    #[test]
+    fn fps_counts_the_last_second() {
+        let (clock, mut timer) = timer();
+        for _ in 0..60 {
+            timer.update();
+        }
+        assert_eq!(timer.fps(), 60);
+        clock.advance_ms(2000);
+        timer.update();
+        assert_eq!(timer.fps(), 1);
+    }
+}
@@ -0,0 +1,359 @@
+use std::io;
+use std::io::{Read, Write, Seek, SeekFrom};
+use std::fs;
+use std::fs::File;
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use resources::ResourceError;
+
+
+// Somewhere files are read from. `None` when the file is not there,
+// so that the next mount is tried.
+pub trait Mount: Send + Sync {
+    fn read(&self, path: &Path) -> Option<io::Result<Vec<u8>>>;
+
+    fn modified(&self, _: &Path) -> Option<SystemTime> { None }
+
+    // The file on disk, for libraries which want a path.
+    fn resolve(&self, _: &Path) -> Option<PathBuf> { None }
+}
+
+
+// Mount points searched from the last mounted to the first,
+// so a mod directory mounted last overrides the files of the game.
+#[derive(Clone)]
+pub struct Vfs {
+    mounts: Vec<(PathBuf, Arc<Mount>)>,
+}
+
+
+impl Vfs {
+    pub fn new() -> Vfs {
+        Vfs { mounts: Vec::new() }
+    }
+
+    // `path` under `point` is looked up as `path` in `mount`.
+    pub fn mount<P, M>(mut self, point: P, mount: M) -> Vfs
+            where P: AsRef<Path>, M: Mount + 'static {
+        self.mounts.push((point.as_ref().to_path_buf(), Arc::new(mount)));
+        self
+    }
+
+    fn find<F, R>(&self, path: &Path, f: F) -> Option<R>
+            where F: Fn(&Mount, &Path) -> Option<R> {
+        for &(ref point, ref mount) in self.mounts.iter().rev() {
+            if let Ok(rest) = path.strip_prefix(point) {
+                if let Some(x) = f(&**mount, rest) { return Some (x) }
+            }
+        }
+        None
+    }
+
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>, ResourceError> {
+        match self.find(path, |mount, rest| mount.read(rest)) {
+            Some (Ok (x)) => Ok (x),
+            Some (Err (e)) => Err (ResourceError::Decode(path.to_path_buf(), e.to_string())),
+            None => Err (ResourceError::NotFound(path.to_path_buf())),
+        }
+    }
+
+    pub fn modified(&self, path: &Path) -> Option<SystemTime> {
+        self.find(path, |mount, rest| mount.modified(rest))
+    }
+
+    pub fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        self.find(path, |mount, rest| mount.resolve(rest))
+    }
+}
+
+
+// Directory of the executable.
+pub fn exe_dir() -> PathBuf {
+    let exe = ::std::env::current_exe().unwrap_or(PathBuf::new());
+    exe.parent().map(|x| x.to_path_buf()).unwrap_or(PathBuf::new())
+}
+
+
+pub struct Directory {
+    root: PathBuf,
+}
+
+
+impl Directory {
+    pub fn new<P: AsRef<Path>>(root: P) -> Directory {
+        Directory { root: root.as_ref().to_path_buf() }
+    }
+
+    // Next to the executable, whatever the working directory is.
+    pub fn exe_relative<P: AsRef<Path>>(path: P) -> Directory {
+        Directory::new(exe_dir().join(path))
+    }
+}
+
+
+impl Mount for Directory {
+    fn read(&self, path: &Path) -> Option<io::Result<Vec<u8>>> {
+        let path = self.root.join(path);
+        if !path.is_file() { return None }
+        let mut data = Vec::new();
+        Some (File::open(path).and_then(|mut x| x.read_to_end(&mut data)).map(|_| data))
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        fs::metadata(self.root.join(path)).and_then(|x| x.modified()).ok()
+    }
+
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let path = self.root.join(path);
+        if path.is_file() { Some (path) } else { None }
+    }
+}
+
+
+// Pack archive: `PACK`, the number of entries, then for each entry
+// its name length, name, offset and length, then the data of the entries.
+// Numbers are little endian, u32 but the offsets and lengths in u64.
+const MAGIC: &'static [u8] = b"PACK";
+
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut b = [0u8; 4];
+    try!(read_exact(r, &mut b));
+    Ok (b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+}
+
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let low = try!(read_u32(r)) as u64;
+    let high = try!(read_u32(r)) as u64;
+    Ok (low | high << 32)
+}
+
+
+fn read_exact<R: Read>(r: &mut R, buffer: &mut [u8]) -> io::Result<()> {
+    let mut done = 0;
+    while done < buffer.len() {
+        match try!(r.read(&mut buffer[done..])) {
+            0 => return Err (io::Error::new(io::ErrorKind::Other, "unexpected end of pack")),
+            n => done += n,
+        }
+    }
+    Ok (())
+}
+
+
+fn write_u32<W: Write>(w: &mut W, x: u32) -> io::Result<()> {
+    w.write_all(&[x as u8, (x >> 8) as u8, (x >> 16) as u8, (x >> 24) as u8])
+}
+
+
+fn write_u64<W: Write>(w: &mut W, x: u64) -> io::Result<()> {
+    try!(write_u32(w, x as u32));
+    write_u32(w, (x >> 32) as u32)
+}
+
+
+// Entry names use `/`, whatever the platform.
+fn entry_name(path: &Path) -> String {
+    let parts: Vec<String> = path.components()
+        .map(|x| x.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    parts.join("/")
+}
+
+
+pub struct Pack {
+    file: PathBuf,
+    modified: Option<SystemTime>,
+    index: BTreeMap<String, (u64, u64)>,
+}
+
+
+impl Pack {
+    pub fn open<P: AsRef<Path>>(file: P) -> io::Result<Pack> {
+        let file = file.as_ref().to_path_buf();
+        let mut reader = io::BufReader::new(try!(File::open(&file)));
+        let mut magic = [0u8; 4];
+        try!(read_exact(&mut reader, &mut magic));
+        if &magic[..] != MAGIC {
+            return Err (io::Error::new(io::ErrorKind::Other, "not a pack"));
+        }
+        let count = try!(read_u32(&mut reader));
+        let mut index = BTreeMap::new();
+        for _ in 0..count {
+            let len = try!(read_u32(&mut reader)) as usize;
+            let mut name = vec![0u8; len];
+            try!(read_exact(&mut reader, &mut name));
+            let name = match String::from_utf8(name) {
+                Ok (x) => x,
+                Err (_) => return Err (io::Error::new(io::ErrorKind::Other, "bad entry name")),
+            };
+            let offset = try!(read_u64(&mut reader));
+            let length = try!(read_u64(&mut reader));
+            index.insert(name, (offset, length));
+        }
+        let modified = fs::metadata(&file).and_then(|x| x.modified()).ok();
+        Ok (Pack { file: file, modified: modified, index: index })
+    }
+
+    pub fn entries(&self) -> Vec<&str> {
+        self.index.keys().map(|x| &x[..]).collect()
+    }
+
+    fn read_entry(&self, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+        let mut file = try!(File::open(&self.file));
+        try!(file.seek(SeekFrom::Start(offset)));
+        let mut data = vec![0u8; length as usize];
+        try!(read_exact(&mut file, &mut data));
+        Ok (data)
+    }
+}
+
+
+impl Mount for Pack {
+    fn read(&self, path: &Path) -> Option<io::Result<Vec<u8>>> {
+        let &(offset, length) = match self.index.get(&entry_name(path)) {
+            Some (x) => x,
+            None => return None,
+        };
+        Some (self.read_entry(offset, length))
+    }
+
+    // Every entry changes with the pack.
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        if self.index.contains_key(&entry_name(path)) { self.modified } else { None }
+    }
+}
+
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in try!(fs::read_dir(root.join(dir))) {
+        let path = dir.join(try!(entry).file_name());
+        if root.join(&path).is_dir() {
+            try!(walk(root, &path, files));
+        }
+        else {
+            files.push(path);
+        }
+    }
+    Ok (())
+}
+
+
+// Packs every file under `root`, named relative to it. Returns the number of files.
+pub fn write_pack(root: &Path, out: &Path) -> io::Result<usize> {
+    let mut files = Vec::new();
+    try!(walk(root, Path::new(""), &mut files));
+    files.sort();
+
+    let names: Vec<String> = files.iter().map(|x| entry_name(x)).collect();
+    let mut lengths = Vec::with_capacity(files.len());
+    for file in &files {
+        lengths.push(try!(fs::metadata(root.join(file))).len());
+    }
+    // The data starts right after the index.
+    let mut offset = (MAGIC.len() + 4) as u64;
+    for name in &names {
+        offset += 4 + name.len() as u64 + 8 + 8;
+    }
+
+    let mut writer = io::BufWriter::new(try!(File::create(out)));
+    try!(writer.write_all(MAGIC));
+    try!(write_u32(&mut writer, files.len() as u32));
+    for (name, &length) in names.iter().zip(lengths.iter()) {
+        try!(write_u32(&mut writer, name.len() as u32));
+        try!(writer.write_all(name.as_bytes()));
+        try!(write_u64(&mut writer, offset));
+        try!(write_u64(&mut writer, length));
+        offset += length;
+    }
+    for file in &files {
+        let mut data = Vec::new();
+        try!(File::open(root.join(file)).and_then(|mut x| x.read_to_end(&mut data)));
+        try!(writer.write_all(&data));
+    }
+    try!(writer.flush());
+    Ok (files.len())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::io::Write;
+    use std::fs;
+    use std::fs::File;
+    use std::env::temp_dir;
+    use std::path::{Path, PathBuf};
+    use resources::ResourceError;
+    use super::{Vfs, Mount, Pack, write_pack};
+
+    // Files in memory, by name.
+    struct Files(Vec<(&'static str, &'static str)>);
+
+    impl Mount for Files {
+        fn read(&self, path: &Path) -> Option<io::Result<Vec<u8>>> {
+            self.0.iter().find(|x| Path::new(x.0) == path).map(|x| Ok (x.1.as_bytes().to_vec()))
+        }
+    }
+
+    fn not_found(vfs: &Vfs, path: &str) -> bool {
+        match vfs.read(Path::new(path)) {
+            Err (ResourceError::NotFound(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn fresh_dir(name: &str) -> PathBuf {
+        let dir = temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn pack_round_trip() {
+        let dir = fresh_dir("engine-vfs-pack");
+        let root = dir.join("assets");
+        fs::create_dir_all(root.join("tile")).unwrap();
+        File::create(root.join("a.txt")).unwrap().write_all(b"alpha").unwrap();
+        File::create(root.join("tile/b.txt")).unwrap().write_all(b"beta").unwrap();
+        let out = dir.join("assets.pack");
+        assert_eq!(write_pack(&root, &out).unwrap(), 2);
+
+        let pack = Pack::open(&out).unwrap();
+        assert_eq!(pack.entries(), vec!["a.txt", "tile/b.txt"]);
+        assert_eq!(pack.read(Path::new("tile/b.txt")).unwrap().unwrap(), b"beta");
+        assert!(pack.read(Path::new("c.txt")).is_none());
+        assert!(pack.modified(Path::new("a.txt")).is_some());
+        assert!(Pack::open(root.join("a.txt")).is_err());
+
+        let vfs = Vfs::new().mount("assets", pack);
+        assert_eq!(vfs.read(Path::new("assets/a.txt")).unwrap(), b"alpha");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn later_mount_overrides() {
+        let vfs = Vfs::new()
+            .mount("", Files(vec![("a.txt", "game"), ("b.txt", "game")]))
+            .mount("", Files(vec![("a.txt", "mod")]));
+        assert_eq!(vfs.read(Path::new("a.txt")).unwrap(), b"mod");
+        assert_eq!(vfs.read(Path::new("b.txt")).unwrap(), b"game");
+        assert!(not_found(&vfs, "c.txt"));
+    }
+
+    #[test]
+    fn mount_point() {
+        let vfs = Vfs::new()
+            .mount("assets", Files(vec![("a.txt", "assets")]))
+            .mount("assets/tile", Files(vec![("a.txt", "tile")]));
+        assert_eq!(vfs.read(Path::new("assets/a.txt")).unwrap(), b"assets");
+        assert_eq!(vfs.read(Path::new("assets/tile/a.txt")).unwrap(), b"tile");
+        assert!(not_found(&vfs, "a.txt"));
+        assert!(not_found(&vfs, "other/a.txt"));
+        assert!(not_found(&vfs, "assets/tile/tile/a.txt"));
+    }
+}
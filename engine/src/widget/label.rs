@@ -1,12 +1,12 @@
 use na::Vec2;
 use std::string::ToString;
-use glium::Display;
 use sprite::Sprite;
 use canvas::Canvas;
 use widget::WidgetBuilder;
 use text;
 use text::{TextStyle, GlyphCache};
 use event::EventStream;
+use profiler;
 
 
 #[derive(Clone)]
@@ -44,6 +44,7 @@ impl Label {
 
 impl WidgetBuilder for Label {
     fn render(&self) -> Canvas {
+        let _scope = profiler::scope("text render");
         let glyphs = {
             let mut cache = self.cache.lock().unwrap();
             text::load(&mut *cache, &self.style, &self.text)
@@ -58,9 +59,29 @@ impl WidgetBuilder for Label {
         (stream, None)
     }
 
-    fn sprite(&self, display: &Display, canvas: Canvas) -> Sprite {
-        canvas.into_sprite(display)
-            .position(self.position)
+    fn place(&self, sprite: Sprite) -> Sprite {
+        sprite.position(self.position)
             .anchor(self.anchor)
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use image;
+    use text::{TextStyle, GlyphCache};
+    use widget::WidgetBuilder;
+    use super::Label;
+
+    // "Sansa" in the built-in font at 18 px, black on transparent. Regenerate
+    // with `save_png` after a deliberate change to the typesetting.
+    const SANSA: &'static [u8] = include_bytes!("../golden/label.png");
+
+    #[test]
+    fn builtin_font() {
+        let canvas = Label::new(GlyphCache::new(), TextStyle::builtin(), "Sansa").render();
+        let golden = image::load_from_memory(SANSA).unwrap().to_rgba();
+        assert_eq!(canvas.to_image().dimensions(), golden.dimensions());
+        assert!(canvas.to_image().into_raw() == golden.into_raw());
+    }
+}
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+use std::cell::RefCell;
 use glium::{Display, Frame};
 use sprite::Sprite;
 use render::{Renderer, Renderable};
@@ -6,20 +8,34 @@ use timer::Ms;
 use canvas::Canvas;
 use math::Mat;
 use id::Id;
+use animation::State;
+use atlas::{Atlas, Region};
 
 pub mod label;
+pub mod overlay;
 
 pub use self::label::Label;
+pub use self::overlay::Overlay;
 
 
 pub trait WidgetBuilder: Sized {
-    fn sprite(&self, &Display, Canvas) -> Sprite;
+    // Where the sprite of the canvas goes, its position and anchor.
+    fn place(&self, Sprite) -> Sprite;
     fn render(&self) -> Canvas;
     fn event_respond(&self, EventStream, &mut Sprite) -> (EventStream, Option<Self>);
 
+    fn sprite(&self, display: &Display, canvas: Canvas) -> Sprite {
+        self.place(canvas.into_sprite(display))
+    }
+
     fn build(self, display: &Display) -> Widget<Self> {
         Widget::new(display, self)
     }
+
+    // Drawn from a page of `atlas`, see `Widget::in_atlas`.
+    fn build_in(self, display: &Display, atlas: &Rc<RefCell<Atlas>>) -> Widget<Self> {
+        Widget::in_atlas(display, self, atlas.clone())
+    }
 }
 
 
@@ -28,6 +44,8 @@ pub struct Widget<B: WidgetBuilder> {
     visible: bool,
     sprite: Sprite,
     pub builder: B,
+    // Freed when the widget is dropped or rebuilt.
+    region: Option<(Rc<RefCell<Atlas>>, Region)>,
 }
 
 
@@ -43,8 +61,31 @@ impl<B: WidgetBuilder> Widget<B> {
             visible: true,
             sprite: builder.sprite(display, canvas),
             builder: builder,
+            region: None,
+        }
+    }
+
+    // Widgets of the same atlas page batch together. Its own texture
+    // if the canvas is bigger than a page.
+    pub fn in_atlas(display: &Display, builder: B, atlas: Rc<RefCell<Atlas>>) -> Widget<B> {
+        let canvas = builder.render();
+        if !atlas.borrow().fits(&canvas) {
+            return Widget::with_canvas(display, builder, canvas);
+        }
+        let region = atlas.borrow_mut().insert(display, canvas).unwrap();
+        let sprite = builder.place(atlas.borrow().sprite(&region));
+        Widget {
+            id: Id::new(),
+            visible: true,
+            sprite: sprite,
+            builder: builder,
+            region: Some ((atlas, region)),
         }
     }
+
+    pub fn set_state(&mut self, state: State<Sprite>) {
+        self.sprite.set_state(state);
+    }
 }
 
 impl<B: WidgetBuilder> Renderable for Widget<B> {
@@ -62,7 +103,10 @@ impl<B: WidgetBuilder> Update for Widget<B> {
     {
         let (mut stream, rebuilder) = self.builder.event_respond(stream, &mut self.sprite);
         if let Some(new) = rebuilder {
-            *self = Widget::new(renderer.display, new)
+            *self = match self.region {
+                Some ((ref atlas, _)) => Widget::in_atlas(renderer.display, new, atlas.clone()),
+                None => Widget::new(renderer.display, new),
+            }
         }
         else {
             stream = self.sprite.update(renderer, delta, stream);
@@ -70,3 +114,12 @@ impl<B: WidgetBuilder> Update for Widget<B> {
         return stream;
     }
 }
+
+
+impl<B: WidgetBuilder> Drop for Widget<B> {
+    fn drop(&mut self) {
+        if let Some((ref atlas, ref region)) = self.region {
+            atlas.borrow_mut().free(region);
+        }
+    }
+}
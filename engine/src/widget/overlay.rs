@@ -0,0 +1,139 @@
+use na::Vec2;
+use sprite::Sprite;
+use canvas::Canvas;
+use color::Color;
+use widget::WidgetBuilder;
+use text;
+use text::{TextStyle, GlyphCache};
+use event::{Event, EventStream, WindowEvent};
+use profiler;
+use profiler::{Profiler, FRAME};
+
+
+const GRAPH_HEIGHT: usize = 48;
+// Frame time at the top of the graph, twice a 60 Hz frame.
+const GRAPH_MS: f32 = 33.3;
+
+
+fn bar_color(ms: f32) -> Color {
+    if ms <= 1000.0 / 60.0 { Color::new(0.3, 0.9, 0.3, 1.0) }
+    else if ms <= 1000.0 / 30.0 { Color::new(0.9, 0.8, 0.2, 1.0) }
+    else { Color::new(0.9, 0.3, 0.3, 1.0) }
+}
+
+
+// FPS, frame-time graph, scope timings and renderer counts,
+// a snapshot of the profiler taken by `refresh`. F3 shows or hides it.
+#[derive(Clone)]
+pub struct Overlay {
+    cache: GlyphCache,
+    style: TextStyle,
+    position: Vec2<f32>,
+    anchor: Vec2<f32>,
+    shown: bool,
+    text: String,
+    frames: Vec<f32>,
+}
+
+
+impl Overlay {
+    pub fn new(cache: GlyphCache, style: TextStyle) -> Overlay {
+        let mut style = style;
+        style.color = Color::white();
+        Overlay {
+            cache: cache,
+            style: style,
+            position: ::na::zero(),
+            anchor: ::na::zero(),
+            shown: false,
+            text: String::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn anchor(self, center: Vec2<f32>) -> Overlay {
+        Overlay { anchor: center, ..self }
+    }
+
+    pub fn position(self, position: Vec2<f32>) -> Overlay {
+        Overlay { position: position, ..self }
+    }
+
+    // Another font, drawn in white all the same.
+    pub fn style(self, style: TextStyle) -> Overlay {
+        let mut style = style;
+        style.color = Color::white();
+        Overlay { style: style, ..self }
+    }
+
+    pub fn shown(&self) -> bool {
+        self.shown
+    }
+
+    pub fn refresh(&self, fps: usize) -> Overlay {
+        profiler::with(|profiler: &mut Profiler| {
+            let mut text = format!("FPS {}", fps);
+            for name in profiler.names() {
+                if let Some(x) = profiler.stats(name) {
+                    text.push_str(&format!("\n{:12} {:5.2} avg {:5.2} min {:5.2} p99 {:5.2}",
+                                           name, x.last, x.avg, x.min, x.p99));
+                }
+            }
+            text.push_str(&format!("\ndraw calls {}  sprites {}",
+                                   profiler.counter("draw calls"),
+                                   profiler.counter("sprites")));
+            Overlay { text: text, frames: profiler.history(FRAME), ..self.clone() }
+        })
+    }
+}
+
+
+impl WidgetBuilder for Overlay {
+    fn render(&self) -> Canvas {
+        if !self.shown { return Canvas::new(1, 1) }
+        let _scope = profiler::scope("text render");
+        let label = {
+            let glyphs = {
+                let mut cache = self.cache.lock().unwrap();
+                text::load(&mut *cache, &self.style, &self.text)
+            };
+            let xs = glyphs.iter().map(|&(c, ref g)| (c, &**g)).collect();
+            text::draw(&self.style, xs)
+        };
+        let width = ::std::cmp::max(label.width, self.frames.len());
+        let height = label.height + GRAPH_HEIGHT;
+        let background = Color::new(0.0, 0.0, 0.0, 0.6);
+        let mut canvas = Canvas::with_color(width, height, background);
+        canvas.blit(&label, (0, 0));
+        // One bar per frame, the newest on the right.
+        let skip = width - self.frames.len();
+        for (i, &ms) in self.frames.iter().enumerate() {
+            let bar = ((ms / GRAPH_MS).min(1.0) * GRAPH_HEIGHT as f32) as i32;
+            canvas.fill_rect(((skip + i) as i32, height as i32 - bar), (1, bar), bar_color(ms));
+        }
+        canvas.factor(self.style.hidpi_factor)
+    }
+
+    fn event_respond(&self, stream: EventStream, _: &mut Sprite)
+        -> (EventStream, Option<Overlay>)
+    {
+        use glium::glutin::ElementState::Released;
+        use glium::glutin::VirtualKeyCode::F3;
+
+        let mut toggle = false;
+        for event in stream.iter() {
+            if let &Event::Window(WindowEvent::KeyboardInput(Released, _, Some(F3))) = event {
+                toggle = !toggle;
+            }
+        }
+        let rebuild = if toggle {
+            Some (Overlay { shown: !self.shown, ..self.clone() })
+        } else { None };
+        (stream, rebuild)
+    }
+
+    fn place(&self, sprite: Sprite) -> Sprite {
+        sprite.position(self.position)
+            .anchor(self.anchor)
+    }
+}
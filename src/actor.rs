@@ -0,0 +1,171 @@
+use std::collections::{HashMap, HashSet};
+use glium::Frame;
+use na::Vec2;
+use engine::{Sprite, Renderer, Renderable, Update, EventStream};
+use engine::animation::{State, series};
+use engine::id::Id;
+use engine::color::Color;
+use engine::easing::{Easing, Mode};
+use engine::math::Mat;
+use engine::timer::Ms;
+use engine::sprite::animate;
+use game::{Game, Output};
+use tile::TileGen;
+
+
+const HOP: f32 = 12.0;
+const LUNGE: f32 = 0.4;
+
+
+pub struct Actors {
+    sprites: HashMap<Id, Sprite>,
+    // Roles whose attack may still be playing, see `busy`.
+    attackers: HashSet<Id>,
+    hit: State<Sprite>,
+}
+
+
+impl Actors {
+    pub fn new(game: &Game, tile: &TileGen, hit: State<Sprite>) -> Actors {
+        let mut sprites = HashMap::new();
+        for role in game.roles() {
+            let sprite = tile.tile(role, tile.locate(role.position));
+            sprites.insert(role.id, sprite);
+        }
+        let mut actors = Actors {
+            sprites: sprites,
+            attackers: HashSet::new(),
+            hit: hit,
+        };
+        actors.light(game);
+        actors
+    }
+
+    pub fn hit(&self) -> &State<Sprite> {
+        &self.hit
+    }
+
+    pub fn get(&self, id: &Id) -> Option<&Sprite> {
+        self.sprites.get(id)
+    }
+
+    // Some attack animation is still playing.
+    pub fn busy(&self) -> bool {
+        self.attackers.iter().any(|id| self.sprites.get(id).map_or(false, |x| x.playing()))
+    }
+
+    // Snap the running animation of `id` to its end.
+    fn finish(&mut self, id: &Id) {
+        self.attackers.remove(id);
+        if let Some(sprite) = self.sprites.get_mut(id) {
+            sprite.finish();
+        }
+    }
+
+    fn play(&mut self, id: &Id, state: State<Sprite>) {
+        self.finish(id);
+        if let Some(sprite) = self.sprites.get_mut(id) {
+            sprite.set_state(state);
+        }
+    }
+
+    fn position(&self, id: &Id) -> Option<Vec2<f32>> {
+        self.sprites.get(id).map(|x| x.transform.position)
+    }
+
+    pub fn apply(&mut self, time: Ms, tile: &TileGen, output: &Output) {
+        match *output {
+            Output::Move(ref id, from, to) => {
+                if from == to { return }
+                let a = tile.position(tile.locate(from));
+                let b = tile.position(tile.locate(to));
+                let hop: Vec2<f32> = na![0.0, HOP];
+                self.play(id, animate::curve(time, [a, a+hop, b+hop, b],
+                                             Easing::Quad(Mode::InOut)));
+            }
+            Output::Attack(ref attacker, ref target) => {
+                self.finish(attacker);
+                self.finish(target);
+                let (a, b) = match (self.position(attacker), self.position(target)) {
+                    (Some (a), Some (b)) => (a, b),
+                    _ => return,
+                };
+                let middle = a + (b - a) * LUNGE;
+                let lunge = series(vec![
+                    animate::move_(time/2, a, middle, Easing::Quad(Mode::Out)),
+                    animate::move_(time/2, middle, a, Easing::Quad(Mode::In)),
+                ]);
+                self.play(attacker, lunge);
+                self.attackers.insert(*attacker);
+                let hit = self.hit.clone();
+                self.play(target, hit);
+            }
+            Output::Die(ref id) => {
+                self.play(id, animate::fade_out(time));
+            }
+            Output::Light(_) => {}
+        }
+    }
+
+    // Between the two last steps, back to front like the tiles.
+    pub fn interpolated(&self, alpha: f32) -> Vec<Sprite> {
+        let mut sprites: Vec<Sprite> = self.sprites.values().map(|x| x.interpolated(alpha)).collect();
+        sprites.sort_by(|a, b| {
+            let (a, b) = (a.transform.position.y, b.transform.position.y);
+            b.partial_cmp(&a).unwrap()
+        });
+        sprites
+    }
+
+    // Tint every role by the light on its cell, hide the enemies the player can not see.
+    pub fn light(&mut self, game: &Game) {
+        let roles = game.roles();
+        // Dead roles are dropped once they have faded out.
+        let dead: Vec<Id> = self.sprites.iter()
+            .filter(|&(id, sprite)| {
+                sprite.color_multiply.a == 0.0 && !roles.iter().any(|role| role.id == *id)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &dead {
+            self.sprites.remove(id);
+        }
+        for role in roles {
+            let p = role.position;
+            let light = game.light(p.x, p.y);
+            let seen = role.id == game.player().id || game.visible(p);
+            if let Some(sprite) = self.sprites.get_mut(&role.id) {
+                sprite.color_multiply = Color::new(light.x, light.y, light.z,
+                                                   if seen { 1.0 } else { 0.0 });
+            }
+        }
+    }
+}
+
+
+impl Renderable for Actors {
+    fn draw(&self, renderer: &Renderer, target: &mut Frame, parent: &Mat) {
+        let sprites = self.interpolated(1.0);
+        let sprites: Vec<&Sprite> = sprites.iter().collect();
+        sprites.draw(renderer, target, parent);
+    }
+}
+
+
+impl Update for Actors {
+    fn update(&mut self, renderer: &Renderer, delta: Ms, mut stream: EventStream)
+        -> EventStream
+    {
+        for (_, sprite) in self.sprites.iter_mut() {
+            stream = sprite.update(renderer, delta, stream);
+        }
+        let done: Vec<Id> = self.attackers.iter()
+            .filter(|id| !self.sprites.get(*id).map_or(false, |x| x.playing()))
+            .cloned()
+            .collect();
+        for id in &done {
+            self.attackers.remove(id);
+        }
+        return stream;
+    }
+}
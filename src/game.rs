@@ -1,57 +1,338 @@
 // use std::collections::LinkedList
+use std::collections::HashMap;
 use rand;
 use noise::{Brownian2, perlin2};
+use na::{Vec2, Vec3};
+use engine::id::Id;
 use object::{Block, RoleKind, Role};
+use light::{Light, LightMap, Source};
 
 pub const LAYER: i32 = 8;
+const ENEMY: usize = 16;
+const SIGHT: i32 = 8;
+const TORCH: i32 = 7;
+// Enemies notice the player from this far when the player stands in full light.
+const DETECT: f32 = 10.0;
+// Cells darker than this can not be seen.
+const SEEN: f32 = 0.3;
+const PLAYER_DAMAGE: u32 = 40;
+const ENEMY_DAMAGE: u32 = 8;
+// Noise below which a river turns to lava.
+const LAVA: f32 = -0.45;
+// Noise above which land rises into a wall.
+const WALL: f32 = 0.4;
+const DIRECTION: [(i32, i32); 9] = [
+    ( 0,  0), ( 1,  1), (-1, -1), (-1,  1), ( 1, -1),
+    ( 0,  1), ( 1,  0), (-1,  0), ( 0, -1),
+];
 
 
 pub enum Input {
     Move ((i32, i32)),
+    Torch,
 }
 
 
 pub enum Output {
-    Move ((i32, i32)),
+    Move (Id, Vec3<i32>, Vec3<i32>),
+    Light (Vec<Vec2<i32>>),
+    Attack (Id, Id),
+    Die (Id),
 }
 
+
 pub struct Game {
     turn: u64,
+    level: u32,
+    kills: u32,
+    death: Option<String>,
+    messages: Vec<String>,
     player: Role,
+    enemies: Vec<Role>,
+    // Who stands where, for `get`, see `occupy`.
+    occupants: HashMap<(i32, i32, i32), Id>,
+    stairs: Vec3<i32>,
     map: Map,
+    explored: Vec<bool>,
+    exploration: u64,
+    torch: bool,
+    glows: Vec<Source>,
+    light: LightMap,
 }
 
 
 impl Game {
     pub fn new((x, y): (i32, i32)) -> Game {
-        Game {
-            turn: 0,
-            player: Role::new(RoleKind::Player, na![x/2, y/2, 1]),
-            map: Map::new((x, y)),
+        let start = na![x/2, y/2, 1];
+        let mut map = Map::new((x, y));
+        map.set([start.x, start.y, start.z], Block::Nil);
+        let mut enemies: Vec<Role> = Vec::with_capacity(ENEMY);
+        while enemies.len() < ENEMY {
+            let position = map.random_land();
+            if position == start || enemies.iter().any(|x| x.position == position) { continue }
+            enemies.push(Role::new(RoleKind::Enemy, position));
         }
+        let stairs = map.random_land();
+        let mut glows = map.glows();
+        glows.push(Source::new(stairs, 3, na![0.6, 0.5, 0.2]));
+        let mut game = Game {
+            turn: 0,
+            level: 1,
+            kills: 0,
+            death: None,
+            messages: vec!["Welcome to Sansa.".to_string()],
+            player: Role::new(RoleKind::Player, start),
+            enemies: enemies,
+            occupants: HashMap::new(),
+            stairs: stairs,
+            map: map,
+            explored: vec![false; (x*y) as usize],
+            exploration: 0,
+            torch: true,
+            glows: glows,
+            light: LightMap::new((x, y)),
+        };
+        game.occupy();
+        game.relight();
+        game.explore();
+        return game;
     }
 
     #[inline]
     pub fn get(&self, i: [i32; 3]) -> Unit {
-        let [x, y, z] = i;
-        let position = self.player.position;
         Unit {
-            block: self.map.get([x+position.x, y+position.y, z]),
-            role: if [x, y, z] == [0, 0, position.z] {
-                      Some (self.player.clone())
-                  } else { None }
+            block: self.map.get(i),
+            role: self.occupants.get(&(i[0], i[1], i[2])).and_then(|id| self.role(id)).cloned(),
         }
     }
 
-    pub fn next(&mut self, input: Input) -> Output {
+    fn role(&self, id: &Id) -> Option<&Role> {
+        if self.player.id == *id { return Some (&self.player) }
+        self.enemies.iter().find(|x| x.id == *id)
+    }
+
+    // Fills `occupants` again after roles moved or died.
+    fn occupy(&mut self) {
+        let occupants: HashMap<(i32, i32, i32), Id> = self.roles().into_iter()
+            .map(|x| ((x.position.x, x.position.y, x.position.z), x.id))
+            .collect();
+        self.occupants = occupants;
+    }
+
+    pub fn turn(&self) -> u64 {
+        self.turn
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn kills(&self) -> u32 {
+        self.kills
+    }
+
+    pub fn torch(&self) -> bool {
+        self.torch
+    }
+
+    pub fn messages(&self) -> &[String] {
+        &self.messages
+    }
+
+    // Cause of death, once the player is dead.
+    pub fn death(&self) -> Option<&str> {
+        self.death.as_ref().map(|x| &x[..])
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.death.is_some()
+    }
+
+    pub fn player(&self) -> &Role {
+        &self.player
+    }
+
+    pub fn roles(&self) -> Vec<&Role> {
+        let mut roles = vec![&self.player];
+        roles.extend(self.enemies.iter());
+        roles
+    }
+
+    pub fn enemies(&self) -> &[Role] {
+        &self.enemies
+    }
+
+    pub fn stairs(&self) -> Vec3<i32> {
+        self.stairs
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        self.map.size
+    }
+
+    // The highest block of a column, as seen from above.
+    pub fn surface(&self, x: i32, y: i32) -> Block {
+        (0..LAYER).rev().map(|z| self.map.get([x, y, z]))
+            .find(|block| if let Block::Nil = *block { false } else { true })
+            .unwrap_or(Block::Nil)
+    }
+
+    pub fn explored(&self, x: i32, y: i32) -> bool {
+        let (w, h) = self.map.size;
+        if x < 0 || y < 0 || x >= w || y >= h { return false }
+        self.explored[(y*w + x) as usize]
+    }
+
+    // Changes whenever new cells have been explored.
+    pub fn exploration(&self) -> u64 {
+        self.exploration
+    }
+
+    pub fn light(&self, x: i32, y: i32) -> Light {
+        self.light.get(x, y)
+    }
+
+    // Within sight of the player and lit enough.
+    pub fn visible(&self, position: Vec3<i32>) -> bool {
+        let p = self.player.position;
+        let (x, y) = (position.x - p.x, position.y - p.y);
+        x*x + y*y <= SIGHT*SIGHT && self.light.brightness(position.x, position.y) >= SEEN
+    }
+
+    // The darker the player stands, the closer an enemy has to be.
+    fn detect(&self, enemy: &Role) -> bool {
+        let p = self.player.position;
+        let (x, y) = ((enemy.position.x - p.x) as f32, (enemy.position.y - p.y) as f32);
+        let range = DETECT * self.light.brightness(p.x, p.y);
+        x*x + y*y <= range*range
+    }
+
+    fn sources(&self) -> Vec<Source> {
+        let mut sources = self.glows.clone();
+        if self.torch {
+            sources.push(Source::new(self.player.position, TORCH, na![1.0, 0.85, 0.6]));
+        }
+        sources
+    }
+
+    fn relight(&mut self) -> Vec<Vec2<i32>> {
+        let light = {
+            let map = &self.map;
+            let z = self.player.position.z;
+            LightMap::compute(map.size, &self.sources()[..], |x, y| map.get([x, y, z]).opaque())
+        };
+        let changed = light.diff(&self.light);
+        self.light = light;
+        changed
+    }
+
+    fn explore(&mut self) {
+        let p = self.player.position;
+        let (w, h) = self.map.size;
+        let mut changed = false;
+        for j in -SIGHT..SIGHT+1 {
+            for i in -SIGHT..SIGHT+1 {
+                let (x, y) = (p.x + i, p.y + j);
+                if i*i + j*j > SIGHT*SIGHT { continue }
+                if x < 0 || y < 0 || x >= w || y >= h { continue }
+                if self.light.brightness(x, y) < SEEN { continue }
+                let index = (y*w + x) as usize;
+                if !self.explored[index] {
+                    self.explored[index] = true;
+                    changed = true;
+                }
+            }
+        }
+        if changed { self.exploration += 1 }
+    }
+
+    fn walkable(&self, position: Vec3<i32>) -> bool {
+        let (x, y, z) = (position.x, position.y, position.z);
+        let (w, h) = self.map.size;
+        if x < 0 || y < 0 || x >= w || y >= h { return false }
+        if let Block::Land = self.map.get([x, y, z-1]) {} else { return false }
+        if self.map.get([x, y, z]).opaque() { return false }
+        self.get([x, y, z]).role.is_none()
+    }
+
+    pub fn log<T: ToString>(&mut self, message: T) {
+        self.messages.push(message.to_string());
+    }
+
+    fn attack(&mut self, i: usize, outputs: &mut Vec<Output>) {
+        let health = self.enemies[i].health.saturating_sub(PLAYER_DAMAGE);
+        self.enemies[i].health = health;
+        self.log("You hit the enemy.");
+        outputs.push(Output::Attack(self.player.id, self.enemies[i].id));
+        if health == 0 {
+            let enemy = self.enemies.remove(i);
+            self.kills += 1;
+            self.log("You kill the enemy.");
+            outputs.push(Output::Die(enemy.id));
+        }
+    }
+
+    fn hurt(&mut self, enemy: Id, outputs: &mut Vec<Output>) {
+        let health = self.player.health.saturating_sub(ENEMY_DAMAGE);
+        self.player.health = health;
+        self.log("The enemy hits you.");
+        outputs.push(Output::Attack(enemy, self.player.id));
+        if health == 0 {
+            self.death = Some ("killed by an enemy".to_string());
+            self.log("You die...");
+            outputs.push(Output::Die(self.player.id));
+        }
+    }
+
+    pub fn next(&mut self, input: Input) -> Vec<Output> {
+        let mut outputs = Vec::new();
+        if self.is_over() { return outputs }
         self.turn += 1;
         match input {
             Input::Move ((x, y)) => {
-                self.player.position.x -= x;
-                self.player.position.y -= y;
-                Output::Move((x, y))
+                let from = self.player.position;
+                let to = na![from.x - x, from.y - y, from.z];
+                let target = self.enemies.iter().position(|e| e.position == to);
+                if let Some(i) = target {
+                    self.attack(i, &mut outputs);
+                }
+                else if (x, y) != (0, 0) && self.walkable(to) {
+                    self.player.position = to;
+                    outputs.push(Output::Move(self.player.id, from, to));
+                }
+                self.occupy();
             }
+            Input::Torch => {
+                self.torch = !self.torch;
+                let message = if self.torch { "You light your torch." }
+                              else { "You put out your torch." };
+                self.log(message);
+            }
+        }
+        outputs.push(Output::Light(self.relight()));
+        for i in 0..self.enemies.len() {
+            if self.is_over() { break }
+            let from = self.enemies[i].position;
+            let p = self.player.position;
+            let detected = self.detect(&self.enemies[i]);
+            let (x, y) = if detected {
+                ((p.x - from.x).signum(), (p.y - from.y).signum())
+            } else {
+                DIRECTION[rand::random::<usize>() % DIRECTION.len()]
+            };
+            let to = na![from.x + x, from.y + y, from.z];
+            if detected && to == p {
+                let enemy = self.enemies[i].id;
+                self.hurt(enemy, &mut outputs);
+                continue;
+            }
+            if (x, y) == (0, 0) || !self.walkable(to) { continue }
+            self.enemies[i].position = to;
+            self.occupy();
+            outputs.push(Output::Move(self.enemies[i].id, from, to));
         }
+        self.explore();
+        return outputs;
     }
 }
 
@@ -74,11 +355,16 @@ impl Map {
         let seed = rand::random();
         let noise = Brownian2::new(perlin2, 4).wavelength(32.0);
         let mut data = Vec::with_capacity(size);
+        let mut values = Vec::with_capacity((x*y) as usize);
         for i in 0..y {
             for j in 0..x {
                 let val = noise.apply(&seed, &[i as f32, j as f32]);
+                values.push(val);
                 data.push(
-                    if val < 0.0 {
+                    if val < LAVA {
+                        Block::Lava
+                    }
+                    else if val < 0.0 {
                         Block::River
                     }
                     else {
@@ -87,7 +373,10 @@ impl Map {
                 );
             }
         }
-        for _ in 1..LAYER {
+        for &val in &values {
+            data.push(if val > WALL { Block::Wall } else { Block::Nil });
+        }
+        for _ in 2..LAYER {
             for _ in 0..y {
                 for _ in 0..x {
                     data.push(Block::Nil);
@@ -100,6 +389,33 @@ impl Map {
         }
     }
 
+    fn glows(&self) -> Vec<Source> {
+        let (w, h) = self.size;
+        let mut glows = Vec::new();
+        for z in 0..LAYER {
+            for y in 0..h {
+                for x in 0..w {
+                    if let Some((radius, color)) = self.get([x, y, z]).glow() {
+                        glows.push(Source::new(na![x, y, z], radius, color));
+                    }
+                }
+            }
+        }
+        glows
+    }
+
+    fn random_land(&self) -> Vec3<i32> {
+        let (x, y) = self.size;
+        loop {
+            let position = na![rand::random::<u32>() as i32 % x,
+                               rand::random::<u32>() as i32 % y, 1];
+            if let (Block::Land, Block::Nil) = (self.get([position.x, position.y, 0]),
+                                                self.get([position.x, position.y, 1])) {
+                return position;
+            }
+        }
+    }
+
     #[inline]
     fn index(&self, i: [i32; 3]) -> usize {
         let (a, b) = self.size;
@@ -118,7 +434,6 @@ impl Map {
         }
     }
 
-    #[allow(dead_code)]
     #[inline]
     pub fn set(&mut self, i: [i32; 3], block: Block) {
         if self.index(i) < self.data.len() {
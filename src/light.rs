@@ -0,0 +1,125 @@
+use na;
+use na::{Vec2, Vec3};
+
+
+pub type Light = Vec3<f32>;
+
+pub const AMBIENT: f32 = 0.15;
+
+
+#[derive(Clone, Debug)]
+pub struct Source {
+    pub position: Vec3<i32>,
+    pub radius: i32,
+    pub color: Light,
+}
+
+
+impl Source {
+    pub fn new(position: Vec3<i32>, radius: i32, color: Light) -> Source {
+        Source { position: position, radius: radius, color: color }
+    }
+}
+
+
+pub struct LightMap {
+    size: (i32, i32),
+    data: Vec<Light>,
+}
+
+
+impl LightMap {
+    pub fn new((w, h): (i32, i32)) -> LightMap {
+        LightMap {
+            size: (w, h),
+            data: vec![na![AMBIENT, AMBIENT, AMBIENT]; (w*h) as usize],
+        }
+    }
+
+    // `opaque` tells whether a cell blocks the light passing through it.
+    pub fn compute<F>(size: (i32, i32), sources: &[Source], opaque: F) -> LightMap
+            where F: Fn(i32, i32) -> bool {
+        let mut map = LightMap::new(size);
+        let (w, h) = size;
+        for source in sources {
+            let (x0, y0, r) = (source.position.x, source.position.y, source.radius);
+            for y in y0-r..y0+r+1 {
+                for x in x0-r..x0+r+1 {
+                    if x < 0 || y < 0 || x >= w || y >= h { continue }
+                    let (i, j) = ((x-x0) as f32, (y-y0) as f32);
+                    let distance = (i*i + j*j).sqrt();
+                    if distance > r as f32 { continue }
+                    if !line_of_sight((x0, y0), (x, y), &opaque) { continue }
+                    let falloff = 1.0 - distance / (r as f32 + 1.0);
+                    let index = (y*w + x) as usize;
+                    map.data[index] = map.data[index] + source.color * falloff * falloff;
+                }
+            }
+        }
+        for light in map.data.iter_mut() {
+            *light = na![light.x.min(1.0), light.y.min(1.0), light.z.min(1.0)];
+        }
+        map
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Light {
+        let (w, h) = self.size;
+        if x < 0 || y < 0 || x >= w || y >= h {
+            return na![AMBIENT, AMBIENT, AMBIENT];
+        }
+        self.data[(y*w + x) as usize]
+    }
+
+    pub fn brightness(&self, x: i32, y: i32) -> f32 {
+        let light = self.get(x, y);
+        light.x.max(light.y).max(light.z)
+    }
+
+    // Cells whose light differs from `other`.
+    pub fn diff(&self, other: &LightMap) -> Vec<Vec2<i32>> {
+        let (w, _) = self.size;
+        let mut cells = Vec::new();
+        for (i, (a, b)) in self.data.iter().zip(other.data.iter()).enumerate() {
+            if a != b {
+                let i = i as i32;
+                cells.push(na![i % w, i / w]);
+            }
+        }
+        cells
+    }
+}
+
+
+// Bresenham; the end points themselves never block.
+fn line_of_sight<F>((x0, y0): (i32, i32), (x1, y1): (i32, i32), opaque: &F) -> bool
+        where F: Fn(i32, i32) -> bool {
+    let (dx, dy) = ((x1-x0).abs(), -(y1-y0).abs());
+    let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+    let (mut x, mut y) = (x0, y0);
+    let mut error = dx + dy;
+    loop {
+        if (x, y) == (x1, y1) { return true }
+        if (x, y) != (x0, y0) && opaque(x, y) { return false }
+        let e2 = error * 2;
+        if e2 >= dy { error += dy; x += sx }
+        if e2 <= dx { error += dx; y += sy }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{LightMap, Source, AMBIENT};
+
+    #[test]
+    fn occlusion() {
+        let sources = [Source::new(na![0, 2, 1], 4, na![1.0, 1.0, 1.0])];
+        let open = LightMap::compute((5, 5), &sources, |_, _| false);
+        let walled = LightMap::compute((5, 5), &sources, |x, y| (x, y) == (2, 2));
+        assert!(open.brightness(4, 2) > AMBIENT);
+        assert_eq!(walled.brightness(4, 2), AMBIENT);
+        // The wall itself is lit, and so is the cell it does not cover.
+        assert_eq!(walled.brightness(2, 2), open.brightness(2, 2));
+        assert_eq!(walled.brightness(3, 0), open.brightness(3, 0));
+    }
+}
@@ -14,21 +14,46 @@ extern crate engine;
 mod object;
 mod game;
 mod tile;
+mod actor;
+mod minimap;
+mod light;
+mod morgue;
+mod score;
 
 pub use nalgebra as na;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use na::Vec2;
 use glium::{Display, Surface};
-use engine::{Texture, Manager, WidgetBuilder, Sprite, Update, Label, Renderer,
-             Engine, Camera, Renderable, EventStream, Event, WindowEvent,
-             build_display};
+use engine::{Texture, Manager, WidgetBuilder, Update, Renderer,
+             Engine, Camera, EventStream, Event, WindowEvent,
+             TileMap, Widget, Sheet, Sprite, build_display};
 use engine::timer::Ms;
+use engine::animation::State;
+use engine::color::Color;
+use engine::easing::{Easing, Mode};
+use engine::timeline::Timeline;
+use engine::resources::{Resource, Loader, Live, RELOADED};
+use engine::vfs::{Vfs, Directory, Pack, exe_dir};
+use engine::profiler;
+use engine::text::TextStyle;
 use object::Block;
-use tile::{Tile, TileGen};
+use tile::TileGen;
 use game::{Game, Input, Output};
+use actor::Actors;
+use minimap::Minimap;
+use score::{Score, Table};
 
 const TILE: &'static str = "assets/tile";
-const FONT: &'static str = "assets/font.otf";
+const ASSETS: &'static str = "assets";
+const FONT: &'static str = "font.otf";
+const ANIM: &'static str = "assets/anim";
+const SCORES: &'static str = "scores.txt";
+const MORGUE: &'static str = "morgue.txt";
+const PACK: &'static str = "assets.pack";
+const MODS: &'static str = "mods";
+const MESSAGES_EXPIRED: &'static str = "messages-expired";
+const MESSAGE_TIME: Ms = 4000;
+const PROFILE_REFRESH: &'static str = "profile-refresh";
 
 
 
@@ -37,30 +62,72 @@ fn game_path() -> PathBuf {
 }
 
 
+// Assets are looked up in the mods, then in the release pack, then in the
+// working directory, then next to the executable.
+fn game_vfs() -> Vfs {
+    let vfs = Vfs::new()
+        .mount("", Directory::exe_relative(""))
+        .mount("", Directory::new(game_path()));
+    let vfs = match Pack::open(exe_dir().join(PACK)) {
+        Ok (pack) => vfs.mount("assets", pack),
+        Err (_) => vfs,
+    };
+    vfs.mount("assets", Directory::new(game_path().join(MODS)))
+}
+
+
+// Missing assets crash debug builds, release builds fall back on a placeholder.
+// With `--watch`, assets changed on disk are loaded again.
+fn manager<'a, T: Resource>(display: &'a Display, loader: &Loader, vfs: &Vfs,
+                            path: &str, placeholder: T) -> Manager<'a, T> {
+    let manager = Manager::new(display, PathBuf::from(path)).loader(loader).vfs(vfs);
+    let manager = if std::env::args().any(|x| x == "--watch") { manager.watch() } else { manager };
+    if cfg!(debug_assertions) { manager } else { manager.placeholder(placeholder) }
+}
+
+
 struct Env<'a> {
     #[allow(dead_code)]
     display: &'a Display,
-    font: PathBuf,
+    // `None` when the game has no font of its own.
+    font: Option<Live<TextStyle>>,
     engine: Engine<'a>,
+    fonts: Manager<'a, TextStyle>,
     textures: Manager<'a, Texture>,
+    sheets: Manager<'a, Sheet>,
+    timelines: Manager<'a, Timeline>,
 
 }
 
 
 impl<'a> Env<'a> {
     fn new(display: &'a Display) -> Env<'a> {
-        let path = game_path();
+        let vfs = game_vfs();
+        let engine = Engine::new(display);
+        let loader = engine.loader.clone();
+        let fonts = manager(display, &loader, &vfs, ASSETS, TextStyle::builtin());
         Env {
             display: display,
-            engine: Engine::new(display),
-            textures: Manager::new(display, path.join(TILE)),
-            font: path.join(FONT),
+            font: fonts.live(FONT).ok(),
+            engine: engine,
+            fonts: fonts,
+            textures: manager(display, &loader, &vfs, TILE, Texture::checkerboard(display)),
+            sheets: manager(display, &loader, &vfs, TILE, Sheet::parse(r#"{"frames": []}"#).unwrap()),
+            timelines: manager(display, &loader, &vfs, ANIM, Timeline::parse("").unwrap()),
+        }
+    }
+
+    // The engine's own font if the game has none.
+    fn font(&self) -> TextStyle {
+        match self.font {
+            Some (ref x) => (*x.get()).clone(),
+            None => TextStyle::builtin(),
         }
     }
 
     fn update(&mut self) {
         self.engine.update();
-        std::thread::sleep_ms(5);
+        self.engine.timer.sleep(5);
     }
 
     #[inline]
@@ -70,95 +137,349 @@ impl<'a> Env<'a> {
 }
 
 
+// The flash of a hit, or `fallback` when its timeline does not compile.
+fn hit_state(timelines: &Manager<Timeline>, fallback: State<Sprite>) -> State<Sprite> {
+    use std::io::Write;
+
+    match timelines.get_or_placeholder("hit.anim").compile() {
+        Ok (x) => x,
+        Err (e) => {
+            let _ = writeln!(std::io::stderr(), "hit.anim: {}, using the fallback", e);
+            fallback
+        }
+    }
+}
+
+
+struct Run<'a> {
+    game: Game,
+    ground: TileMap,
+    actors: Actors,
+    minimap: Widget<Minimap>,
+    camera: Camera<'a>,
+}
+
+
+impl<'a> Run<'a> {
+    fn new(display: &'a Display, tile: &TileGen, timelines: &Manager<Timeline>,
+           corner: Vec2<f32>) -> Run<'a> {
+        let game = Game::new((100, 100));
+        let mut ground = TileMap::new(tile.texture.clone(), 16);
+        make_tiles(&game, tile, &mut ground);
+        let mut camera = Camera::new(display);
+        camera.look_at(player_position(&game, tile));
+        Run {
+            actors: Actors::new(&game, tile, hit_state(timelines, State::Nil)),
+            minimap: Minimap::new(&game)
+                .anchor(na![-1.0, 1.0])
+                .position(corner)
+                .build(display),
+            ground: ground,
+            camera: camera,
+            game: game,
+        }
+    }
+
+    fn next(&mut self, display: &Display, tile: &TileGen, time: Ms, input: Input) {
+        let outputs = self.game.next(input);
+        for output in &outputs {
+            self.actors.apply(time, tile, output);
+            if let &Output::Light(ref cells) = output {
+                for cell in cells {
+                    for k in 0..game::LAYER {
+                        update_tiles(&self.game, tile, &mut self.ground, [cell.x, cell.y, k]);
+                    }
+                }
+            }
+        }
+        self.actors.light(&self.game);
+        self.camera.move_to(time, player_position(&self.game, tile),
+                            Easing::Sine(Mode::Out));
+        if let Some(builder) = self.minimap.builder.update(&self.game) {
+            self.minimap = Widget::new(display, builder);
+        }
+    }
+
+    // Builds the sprites again, after their assets changed on disk.
+    fn reload(&mut self, tile: &TileGen, timelines: &Manager<Timeline>) {
+        self.ground.set_texture(tile.texture.clone());
+        make_tiles(&self.game, tile, &mut self.ground);
+        let hit = hit_state(timelines, self.actors.hit().clone());
+        self.actors = Actors::new(&self.game, tile, hit);
+    }
+
+    // Writes the morgue file and records the score, returns the rank and
+    // a line about each file. A failure also goes to the messages.
+    fn record(&mut self, scores: &mut Table) -> (Option<usize>, Vec<String>) {
+        use std::fs::File;
+
+        let path = game_path().join(MORGUE);
+        let mut lines = Vec::new();
+        match File::create(&path).and_then(|mut file| morgue::dump(&self.game, &mut file)) {
+            Ok (()) => lines.push(format!("Morgue file saved to {}.", MORGUE)),
+            Err (e) => {
+                let line = format!("Could not save the morgue file {}: {}", path.display(), e);
+                self.game.log(&line);
+                lines.push(line);
+            }
+        }
+        let rank = scores.insert(Score::new(&self.game));
+        if let Err(e) = scores.save() {
+            let line = format!("Could not save the scores to {}: {}", SCORES, e);
+            self.game.log(&line);
+            lines.push(line);
+        }
+        (rank, lines)
+    }
+}
+
+
+// One fixed step of the simulation, the minimap reads window events
+// and is updated once per frame instead.
+impl<'a> Update for Run<'a> {
+    fn update(&mut self, renderer: &Renderer, delta: Ms, stream: EventStream)
+        -> EventStream
+    {
+        let stream = self.camera.update(renderer, delta, stream);
+        let stream = self.ground.update(renderer, delta, stream);
+        self.actors.update(renderer, delta, stream)
+    }
+}
+
+
+enum Screen {
+    Menu,
+    Play,
+    Dead,
+}
+
+
+fn menu_text(scores: &Table) -> String {
+    let mut text = "Sansa\n\nPress Enter to start, Escape to quit.\n\nHigh scores:".to_string();
+    for (i, score) in scores.scores.iter().enumerate() {
+        text.push_str(&format!("\n{:2}. {:6}  level {}  turn {}  {}",
+                               i + 1, score.points, score.level, score.turns, score.cause));
+    }
+    text
+}
+
+
+fn death_text(game: &Game, rank: Option<usize>, files: &[String]) -> String {
+    let mut text = format!("You died on level {}, {}.\nTurns: {}  Kills: {}\n",
+                           game.level(), game.death().unwrap_or(""),
+                           game.turn(), game.kills());
+    if let Some(rank) = rank {
+        text.push_str(&format!("New high score, rank {}!\n", rank + 1));
+    }
+    for line in files {
+        text.push_str(&format!("{}\n", line));
+    }
+    text.push_str("\nPress Enter to return to the menu.");
+    text
+}
+
+
+fn messages_text(game: &Game) -> String {
+    let messages = game.messages();
+    let skip = if messages.len() > 3 { messages.len() - 3 } else { 0 };
+    messages[skip..].join("\n")
+}
+
+
 fn main() {
     let turn_time = 250;
     let display = build_display("sansa".to_string(), (800, 600));
     let renderer = Renderer::new(&display);
 
     let mut env = Env::new(&display);
+    let mut text_style = env.font();
+    let fps_style = env.font();
 
-    let mut game = game::Game::new((100, 100));
-    let tile = TileGen::new(&env.textures);
+    let mut ui_camera = Camera::new(&display);
+    // Loading screen, the handles keep the assets alive until they are used.
+    let _loading = (env.textures.load_async("block.png"),
+                    env.sheets.load_async("block.json"),
+                    env.timelines.load_async("hit.anim"));
+    while !env.engine.loader.is_idle() {
+        let (done, total) = env.engine.loader.progress();
+        let label = env.engine.label(text_style.clone(), format!("Loading {}/{}", done, total))
+            .build_in(&display, &env.engine.atlas);
+        let mut target = display.draw();
+        target.clear_color(1.0, 1.0, 1.0, 1.0);
+        renderer.render(&mut target, &label, &ui_camera.matrix());
+        target.finish().unwrap();
+        env.update();
+        if EventStream::new(&display).iter().any(|e| match e {
+            &Event::Window(WindowEvent::Closed) => true,
+            _ => false,
+        }) { return }
+    }
+    let mut tile = TileGen::new(&env.textures, &env.sheets);
+    let mut run = Run::new(&display, &tile, &env.timelines, ui_camera.right_bottom());
+    let mut scores = Table::load(game_path().join(SCORES));
+    let mut screen = Screen::Menu;
     let mut last_turn = env.now();
-    let mut ground = make_tiles(&game, &tile);
     let mut offset: Vec2<i32> = na::zero();
-    let mut game_camera = Camera::new(&display);
-    let mut ui_camera = Camera::new(&display);
+    let mut torch = false;
+    let mut expiry = None;
 
-    let test_text = "你们有一个好，全世界跑到什么地方，你们比其他的西方记者啊跑得还快。\n但是呢问来问去的问题啊，too simple，啊，sometimes naïve！";
-    let text_style = engine::text::TextStyle::new(env.font.clone());
-    let fps_style = engine::text::TextStyle::new(env.font.clone());
-    let mut label = env.engine.label(text_style, &test_text)
-            .anchor(na![-1.0, -1.0])
-            .position(ui_camera.right_top())
+    let mut label = env.engine.label(text_style.clone(), menu_text(&scores))
+            .build_in(&display, &env.engine.atlas);
+    let mut overlay = env.engine.overlay(fps_style)
+            .anchor(na![1.0, -1.0])
+            .position(ui_camera.left_top())
             .build(&display);
+    env.engine.scheduler.every(250, || Event::Message(PROFILE_REFRESH.to_string()));
 
     'main: loop {
         let stream = { // update
+            let _scope = profiler::scope("update");
             let stream = EventStream::new(&display);
-            let mut queue: Vec<&mut Update> = Vec::new();
-            queue.push(&mut label);
-            let delta = env.engine.timer.delta;
-            game_camera.update(delta);
-            ui_camera.update(delta);
-            queue.update(&renderer, delta, stream)
+            // The interface keeps the real clock, so it goes on while the game is paused.
+            let stream = env.engine.scheduler.update(&renderer, env.engine.timer.real_delta, stream);
+            let stream = env.fonts.update(&renderer, env.engine.timer.real_delta, stream);
+            let stream = env.textures.update(&renderer, env.engine.timer.real_delta, stream);
+            let stream = env.sheets.update(&renderer, env.engine.timer.real_delta, stream);
+            let stream = env.timelines.update(&renderer, env.engine.timer.real_delta, stream);
+            let stream = label.update(&renderer, env.engine.timer.real_delta, stream);
+            let mut stream = if let Screen::Menu = screen { stream } else {
+                run.minimap.update(&renderer, env.engine.timer.delta, stream)
+            };
+            while let Some(delta) = env.engine.timer.tick() {
+                if let Screen::Menu = screen { continue }
+                let events = run.update(&renderer, delta, EventStream::empty());
+                stream.append(events);
+            }
+            let stream = overlay.update(&renderer, env.engine.timer.real_delta, stream);
+            ui_camera.update(&renderer, env.engine.timer.real_delta, stream)
         };
-        // println!("FPS: {:3}", env.engine.timer.fps());
+        let mut reloaded = false;
         for e in stream.iter() {
             use glium::glutin::ElementState;
+            use glium::glutin::VirtualKeyCode::*;
 
-            if let &Event::Window(ref e) = e {
-                match e {
-                    &WindowEvent::KeyboardInput(ElementState::Released, _, Some(x)) => {
-                        use glium::glutin::VirtualKeyCode::*;
-
-                        offset = match x {
-                            W => na![ 1,  1],
-                            S => na![-1, -1],
-                            A => na![-1,  1],
-                            D => na![ 1, -1],
-                            Q => na![ 0,  1],
-                            E => na![ 1,  0],
-                            Z => na![-1,  0],
-                            X => na![ 0, -1],
-                            _ => na![ 0,  0],
-                        }
+            if let &Event::Something(ref x, ref path) = e {
+                if x == RELOADED && path.downcast_ref::<PathBuf>().map_or(false, |x| x == Path::new(FONT)) {
+                    // Same name, the glyphs of the old font would be drawn otherwise.
+                    env.engine.glyph_cache.forget(&text_style.font);
+                    text_style = env.font();
+                    let builder = overlay.builder.clone().style(env.font());
+                    overlay = Widget::new(&display, builder);
+                }
+                else if x == RELOADED { reloaded = true }
+            }
+            if let &Event::Message(ref x) = e {
+                if x == MESSAGES_EXPIRED {
+                    expiry = None;
+                    label.set_state(engine::sprite::animate::fade_out(500));
+                }
+                if x == PROFILE_REFRESH && overlay.builder.shown() {
+                    let builder = overlay.builder.refresh(env.engine.timer.fps());
+                    overlay = Widget::new(&display, builder);
+                }
+            }
+            let key = match e {
+                &Event::Window(WindowEvent::KeyboardInput(ElementState::Released, _, Some(x))) => x,
+                _ => continue,
+            };
+            match screen {
+                Screen::Menu => match key {
+                    Return => {
+                        run = Run::new(&display, &tile, &env.timelines, ui_camera.right_bottom());
+                        label = env.engine.label(text_style.clone(), messages_text(&run.game))
+                            .anchor(na![-1.0, -1.0])
+                            .position(ui_camera.right_top())
+                            .build_in(&display, &env.engine.atlas);
+                        last_turn = env.now();
+                        screen = Screen::Play;
                     }
+                    Escape => break 'main,
                     _ => {}
+                },
+                Screen::Play => {
+                    let timer = &mut env.engine.timer;
+                    match key {
+                        T => torch = true,
+                        P => if timer.is_paused() { timer.resume() } else { timer.pause() },
+                        // Slow motion, to look at combat animations.
+                        F1 => timer.set_scale(if timer.scale() < 1.0 { 1.0 } else { 0.25 }),
+                        _ => {}
+                    }
+                    offset = match key {
+                        W => na![ 1,  1],
+                        S => na![-1, -1],
+                        A => na![-1,  1],
+                        D => na![ 1, -1],
+                        Q => na![ 0,  1],
+                        E => na![ 1,  0],
+                        Z => na![-1,  0],
+                        X => na![ 0, -1],
+                        _ => na![ 0,  0],
+                    }
                 }
+                Screen::Dead => if let Return = key {
+                    label = env.engine.label(text_style.clone(), menu_text(&scores))
+                        .build_in(&display, &env.engine.atlas);
+                    screen = Screen::Menu;
+                },
+            }
+        }
+        if reloaded {
+            tile = TileGen::new(&env.textures, &env.sheets);
+            if let Screen::Menu = screen {} else {
+                run.reload(&tile, &env.timelines);
             }
         }
         let now = env.now();
-        if now - last_turn >= turn_time {
-            ground = make_tiles(&game, &tile);
-            let output = game.next(Input::Move ((offset.x, offset.y)));
-            last_turn = now;
-            match output {
-                Output::Move(offset) => {
-                    let v = tile.vertical();
-                    let h = tile.horizontal();
-                    game_camera.reset();
-                    game_camera.move_(turn_time, na::cast(match offset {
-                        ( 0,  0) => na![ 0,  0],
-                        ( 1,  1) => na![ 0,  v],
-                        (-1, -1) => na![ 0, -v],
-                        (-1,  1) => na![-h,  0],
-                        ( 1, -1) => na![ h,  0],
-                        ( 0,  1) => na![-h/2,  v/2],
-                        ( 1,  0) => na![ h/2,  v/2],
-                        (-1,  0) => na![-h/2, -v/2],
-                        ( 0, -1) => na![ h/2, -v/2],
-                        _ => unreachable!()
+        if let Screen::Play = screen {
+            // Attacks are resolved only once their animations are over.
+            if now - last_turn >= turn_time && !run.actors.busy() && !env.engine.timer.is_paused() {
+                let input = if torch { Input::Torch } else { Input::Move ((offset.x, offset.y)) };
+                let messages = run.game.messages().len();
+                run.next(&display, &tile, turn_time, input);
+                last_turn = now;
+                offset = na::zero();
+                torch = false;
+                if run.game.is_over() {
+                    if let Some(handle) = expiry.take() {
+                        env.engine.scheduler.cancel(handle);
+                    }
+                    let (rank, files) = run.record(&mut scores);
+                    label = env.engine.label(text_style.clone(), death_text(&run.game, rank, &files))
+                        .build_in(&display, &env.engine.atlas);
+                    screen = Screen::Dead;
+                }
+                else if run.game.messages().len() != messages {
+                    label = env.engine.label(text_style.clone(), messages_text(&run.game))
+                        .anchor(na![-1.0, -1.0])
+                        .position(ui_camera.right_top())
+                        .build_in(&display, &env.engine.atlas);
+                    // Messages fade out once they have been up for a while.
+                    if let Some(handle) = expiry.take() {
+                        env.engine.scheduler.cancel(handle);
+                    }
+                    expiry = Some(env.engine.scheduler.after(MESSAGE_TIME, || {
+                        Event::Message(MESSAGES_EXPIRED.to_string())
                     }));
                 }
             }
-            offset = na::zero();
         }
         // render
+        let scope = profiler::scope("draw");
         let mut target = display.draw();
-        renderer.render(&mut target, &ground.iter().collect::<Vec<_>>(), &game_camera.matrix());
-        renderer.render::<Vec<&Renderable>>(&mut target, &vec![&label], &ui_camera.matrix());
+        if let Screen::Menu = screen {} else {
+            let alpha = env.engine.timer.alpha();
+            let matrix = run.camera.interpolated(alpha);
+            let actors = run.actors.interpolated(alpha);
+            let actors: Vec<&Sprite> = actors.iter().collect();
+            renderer.render(&mut target, &run.ground, &matrix);
+            renderer.render(&mut target, &actors, &matrix);
+            renderer.render(&mut target, &run.minimap, &ui_camera.matrix());
+        }
+        renderer.render(&mut target, &label, &ui_camera.matrix());
+        renderer.render(&mut target, &overlay, &ui_camera.matrix());
         target.finish().unwrap();
+        drop(scope);
         env.update();
         for e in stream.iter() {
             match e {
@@ -170,23 +491,35 @@ fn main() {
 }
 
 
-fn make_tiles(game: &Game, tile: &TileGen) -> Vec<Sprite> {
-    let mut sprites = Vec::new();
+fn make_tiles(game: &Game, tile: &TileGen, tiles: &mut TileMap) {
+    let (w, h) = game.size();
+    tiles.clear();
     for k in 0..game::LAYER {
-        for j in -20..20 {
-            for i in -20..20 {
-                let unit = game.get([i, j, k]);
-                let block = unit.block;
-                let role = unit.role;
-                let pos = (-i+k, -j+k);
-                if let Block::Nil = block {} else {
-                    sprites.push(tile.sprite(block.offset(), pos));
-                }
-                if let Some(role) = role {
-                    sprites.push(tile.sprite(role.offset(), pos));
-                }
+        for j in 0..h {
+            for i in 0..w {
+                update_tiles(game, tile, tiles, [i, j, k]);
             }
         }
     }
-    return sprites;
+}
+
+
+fn update_tiles(game: &Game, tile: &TileGen, tiles: &mut TileMap, cell: [i32; 3]) {
+    let [i, j, k] = cell;
+    let block = game.get(cell).block;
+    let pos = tile.locate(na![i, j, k]);
+    let light = game.light(i, j);
+    let mut sprites = Vec::new();
+    if let Block::Nil = block {} else {
+        let mut sprite = tile.tile(&block, pos);
+        sprite.color_multiply = Color::new(light.x, light.y, light.z, 1.0);
+        sprites.push(sprite);
+    }
+    tiles.set(cell, sprites);
+}
+
+
+fn player_position(game: &Game, tile: &TileGen) -> Vec2<f32> {
+    let position = game.player().position;
+    tile.position(tile.locate(na![position.x, position.y, 0]))
 }
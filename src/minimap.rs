@@ -0,0 +1,205 @@
+use std::rc::Rc;
+use std::cell::Cell;
+use na;
+use na::Vec2;
+use engine::{Sprite, WidgetBuilder, EventStream, Event, WindowEvent};
+use engine::canvas::Canvas;
+use engine::color::Color;
+use object::Block;
+use game::Game;
+
+
+const SCALE: usize = 2;
+const EXPANDED_SCALE: usize = 6;
+const PAN_STEP: f32 = 32.0;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Marker {
+    Player,
+    Enemy,
+    Stairs,
+}
+
+
+impl Marker {
+    fn color(&self) -> Color {
+        match *self {
+            Marker::Player => Color::white(),
+            Marker::Enemy  => Color::new(0.9, 0.3, 0.3, 1.0),
+            Marker::Stairs => Color::new(1.0, 0.9, 0.2, 1.0),
+        }
+    }
+}
+
+
+fn block_color(block: Block) -> Color {
+    match block {
+        Block::Land  => Color::new(0.2, 0.6, 0.2, 1.0),
+        Block::River => Color::new(0.5, 0.5, 0.9, 1.0),
+        Block::Lava  => Color::new(0.9, 0.4, 0.1, 1.0),
+        Block::Wall  => Color::new(0.45, 0.42, 0.4, 1.0),
+        Block::Nil   => Color::new(0.0, 0.0, 0.0, 0.5),
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Minimap {
+    size: (i32, i32),
+    exploration: u64,
+    cells: Rc<Vec<Option<Block>>>,
+    markers: Vec<(Vec2<i32>, Marker)>,
+    expanded: bool,
+    pan: Cell<Vec2<f32>>,
+    position: Vec2<f32>,
+    anchor: Vec2<f32>,
+}
+
+
+impl Minimap {
+    pub fn new(game: &Game) -> Minimap {
+        Minimap {
+            size: game.size(),
+            exploration: 0,
+            cells: Rc::new(Vec::new()),
+            markers: Vec::new(),
+            expanded: false,
+            pan: Cell::new(na::zero()),
+            position: na::zero(),
+            anchor: na::zero(),
+        }.update(game).unwrap()
+    }
+
+    pub fn anchor(self, center: Vec2<f32>) -> Minimap {
+        Minimap { anchor: center, ..self }
+    }
+
+    pub fn position(self, position: Vec2<f32>) -> Minimap {
+        Minimap { position: position, ..self }
+    }
+
+    // Returns a new minimap only if something visible on it has changed.
+    pub fn update(&self, game: &Game) -> Option<Minimap> {
+        let markers = Minimap::markers(game);
+        let explored = game.exploration() != self.exploration || self.cells.is_empty();
+        if !explored && markers == self.markers { return None }
+
+        let cells = if explored {
+            let (w, h) = self.size;
+            let mut cells = Vec::with_capacity((w*h) as usize);
+            for y in 0..h {
+                for x in 0..w {
+                    cells.push(if game.explored(x, y) {
+                        Some (game.surface(x, y))
+                    } else { None });
+                }
+            }
+            Rc::new(cells)
+        } else { self.cells.clone() };
+
+        Some (Minimap {
+            exploration: game.exploration(),
+            cells: cells,
+            markers: markers,
+            ..self.clone()
+        })
+    }
+
+    fn markers(game: &Game) -> Vec<(Vec2<i32>, Marker)> {
+        let mut markers = Vec::new();
+        let stairs = game.stairs();
+        if game.explored(stairs.x, stairs.y) {
+            markers.push((na![stairs.x, stairs.y], Marker::Stairs));
+        }
+        for enemy in game.enemies() {
+            if game.visible(enemy.position) {
+                markers.push((na![enemy.position.x, enemy.position.y], Marker::Enemy));
+            }
+        }
+        let player = game.player().position;
+        markers.push((na![player.x, player.y], Marker::Player));
+        markers
+    }
+
+    fn scale(&self) -> usize {
+        if self.expanded { EXPANDED_SCALE } else { SCALE }
+    }
+
+    // Cells partly out of the canvas are left out, markers on the edge included.
+    fn fill(canvas: &mut Canvas, scale: usize, (x, y): (i32, i32), color: Color) {
+        let scale = scale as i32;
+        let (x, y) = (x * scale, y * scale);
+        if x < 0 || y < 0 { return }
+        if x + scale > canvas.width as i32 || y + scale > canvas.height as i32 { return }
+        canvas.set_rect((x, y), (scale, scale), color);
+    }
+}
+
+
+impl WidgetBuilder for Minimap {
+    fn render(&self) -> Canvas {
+        let (w, h) = self.size;
+        let scale = self.scale();
+        let background = Color::new(0.0, 0.0, 0.0, 0.5);
+        let mut canvas = Canvas::with_color(w as usize * scale, h as usize * scale, background);
+        for y in 0..h {
+            for x in 0..w {
+                if let Some(block) = self.cells[(y*w + x) as usize] {
+                    Minimap::fill(&mut canvas, scale, (x, y), block_color(block));
+                }
+            }
+        }
+        // Markers are a little bigger than cells to stay visible.
+        for &(p, marker) in &self.markers {
+            for &(i, j) in &[(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)] {
+                Minimap::fill(&mut canvas, scale, (p.x+i, p.y+j), marker.color());
+            }
+        }
+        canvas
+    }
+
+    fn event_respond(&self, stream: EventStream, sprite: &mut Sprite)
+        -> (EventStream, Option<Minimap>)
+    {
+        use glium::glutin::ElementState::{Pressed, Released};
+        use glium::glutin::VirtualKeyCode::*;
+
+        let mut rebuild = None;
+        for e in stream.iter() {
+            match e {
+                &Event::Window(WindowEvent::KeyboardInput(Released, _, Some(M))) => {
+                    rebuild = Some (Minimap {
+                        expanded: !self.expanded,
+                        pan: Cell::new(na::zero()),
+                        ..self.clone()
+                    });
+                }
+                &Event::Window(WindowEvent::KeyboardInput(Pressed, _, Some(key)))
+                        if self.expanded => {
+                    let step = match key {
+                        Left  => na![ PAN_STEP, 0.0],
+                        Right => na![-PAN_STEP, 0.0],
+                        Up    => na![0.0, -PAN_STEP],
+                        Down  => na![0.0,  PAN_STEP],
+                        _ => continue,
+                    };
+                    self.pan.set(self.pan.get() + step);
+                    sprite.transform.position = self.pan.get();
+                }
+                _ => {}
+            }
+        }
+        (stream, rebuild)
+    }
+
+    fn place(&self, sprite: Sprite) -> Sprite {
+        if self.expanded {
+            sprite.position(self.pan.get())
+        }
+        else {
+            sprite.position(self.position)
+                .anchor(self.anchor)
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::io;
+use std::io::Write;
+use object::Block;
+use game::Game;
+
+
+const MESSAGES: usize = 12;
+const RADIUS: i32 = 12;
+
+
+fn cell(game: &Game, x: i32, y: i32) -> char {
+    if !game.explored(x, y) { return ' ' }
+    let player = game.player().position;
+    if (x, y) == (player.x, player.y) { return '@' }
+    for enemy in game.enemies() {
+        if (x, y) == (enemy.position.x, enemy.position.y) && game.visible(enemy.position) {
+            return 'E'
+        }
+    }
+    let stairs = game.stairs();
+    if (x, y) == (stairs.x, stairs.y) { return '>' }
+    match game.surface(x, y) {
+        Block::Land  => '.',
+        Block::River => '~',
+        Block::Lava  => '=',
+        Block::Wall  => '#',
+        Block::Nil   => ' ',
+    }
+}
+
+
+// The first failed write stops it.
+pub fn dump<W: Write>(game: &Game, out: &mut W) -> io::Result<()> {
+    let player = game.player();
+
+    try!(writeln!(out, "Sansa character dump"));
+    try!(writeln!(out, ""));
+    try!(match game.death() {
+        Some (cause) => writeln!(out, "Died on level {}, {}.", game.level(), cause),
+        None => writeln!(out, "Still alive on level {}.", game.level()),
+    });
+    try!(writeln!(out, "Turns: {}", game.turn()));
+    try!(writeln!(out, "Kills: {}", game.kills()));
+    try!(writeln!(out, "Health: {}", player.health));
+    try!(writeln!(out, ""));
+
+    try!(writeln!(out, "Inventory:"));
+    try!(writeln!(out, "  a torch ({})", if game.torch() { "lit" } else { "unlit" }));
+    try!(writeln!(out, ""));
+
+    try!(writeln!(out, "Last messages:"));
+    let messages = game.messages();
+    let skip = if messages.len() > MESSAGES { messages.len() - MESSAGES } else { 0 };
+    for message in &messages[skip..] {
+        try!(writeln!(out, "  {}", message));
+    }
+    try!(writeln!(out, ""));
+
+    try!(writeln!(out, "Map:"));
+    let p = player.position;
+    for y in p.y-RADIUS..p.y+RADIUS+1 {
+        let line: String = (p.x-RADIUS..p.x+RADIUS+1).map(|x| cell(game, x, y)).collect();
+        try!(writeln!(out, "  {}", line.trim_right()));
+    }
+    Ok (())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::io::Write;
+    use game::Game;
+    use super::{dump, RADIUS};
+
+    // Fails every write.
+    struct Full;
+
+    impl Write for Full {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err (io::Error::new(io::ErrorKind::Other, "disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok (())
+        }
+    }
+
+    #[test]
+    fn output() {
+        let mut game = Game::new((40, 40));
+        for i in 0..15 {
+            game.log(format!("message {}", i));
+        }
+        let mut out = Vec::new();
+        dump(&game, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(&lines[..6], &["Sansa character dump", "", "Still alive on level 1.",
+                                  "Turns: 0", "Kills: 0", "Health: 100"]);
+        assert!(lines.contains(&"  a torch (lit)"));
+        // The last twelve messages only.
+        assert!(lines.contains(&"  message 3"));
+        assert!(!lines.contains(&"  message 2"));
+        let map = lines.iter().position(|x| *x == "Map:").unwrap();
+        assert_eq!(lines.len(), map + 1 + 2 * RADIUS as usize + 1);
+        assert_eq!(lines[map + 1 + RADIUS as usize].chars().nth(2 + RADIUS as usize), Some ('@'));
+    }
+
+    #[test]
+    fn failed_write() {
+        assert!(dump(&Game::new((40, 40)), &mut Full).is_err());
+    }
+}
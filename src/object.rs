@@ -1,4 +1,6 @@
 use na::Vec3;
+use engine::id::Id;
+use light::Light;
 
 use self::Block::*;
 
@@ -8,6 +10,28 @@ pub enum Block {
     Nil,
     Land,
     River,
+    // Pools in the deepest parts of rivers, lit by themselves.
+    Lava,
+    // Rock on the highest land, in the way of roles and light.
+    Wall,
+}
+
+
+impl Block {
+    pub fn opaque(&self) -> bool {
+        match *self {
+            Wall => true,
+            Nil | Land | River | Lava => false,
+        }
+    }
+
+    // Radius and colour of the light a block gives off.
+    pub fn glow(&self) -> Option<(i32, Light)> {
+        match *self {
+            Lava => Some ((4, na![0.9, 0.35, 0.1])),
+            Nil | Land | River | Wall => None,
+        }
+    }
 }
 
 
@@ -20,6 +44,7 @@ pub enum RoleKind {
 
 #[derive(Clone, Debug)]
 pub struct Role {
+    pub id: Id,
     pub health: u32,
     pub kind: RoleKind,
     pub position: Vec3<i32>,
@@ -29,6 +54,7 @@ pub struct Role {
 impl Role {
     pub fn new(kind: RoleKind, position: Vec3<i32>) -> Role {
         Role {
+            id: Id::new(),
             kind: kind,
             health: 100,
             position: position,
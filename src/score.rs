@@ -0,0 +1,137 @@
+use std::io;
+use std::io::{Read, Write};
+use std::fs::File;
+use std::path::PathBuf;
+use game::Game;
+
+
+const MAX: usize = 10;
+
+
+#[derive(Clone, Debug)]
+pub struct Score {
+    pub points: u64,
+    pub turns: u64,
+    pub level: u32,
+    pub cause: String,
+}
+
+
+impl Score {
+    pub fn new(game: &Game) -> Score {
+        Score {
+            points: game.kills() as u64 * 100 + game.level() as u64 * 1000 + game.turn(),
+            turns: game.turn(),
+            level: game.level(),
+            cause: game.death().unwrap_or("quit").to_string(),
+        }
+    }
+
+    fn parse(line: &str) -> Option<Score> {
+        let fields: Vec<&str> = line.splitn(4, '\t').collect();
+        if fields.len() != 4 { return None }
+        match (fields[0].parse(), fields[1].parse(), fields[2].parse()) {
+            (Ok (points), Ok (turns), Ok (level)) => Some (Score {
+                points: points,
+                turns: turns,
+                level: level,
+                cause: fields[3].to_string(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+
+impl ToString for Score {
+    fn to_string(&self) -> String {
+        format!("{}\t{}\t{}\t{}", self.points, self.turns, self.level, self.cause)
+    }
+}
+
+
+// Best runs first, stored one per line.
+pub struct Table {
+    path: PathBuf,
+    pub scores: Vec<Score>,
+}
+
+
+impl Table {
+    // A missing or unreadable file is an empty table.
+    pub fn load(path: PathBuf) -> Table {
+        let mut text = String::new();
+        if let Ok(mut file) = File::open(&path) {
+            let _ = file.read_to_string(&mut text);
+        }
+        let scores = text.lines().filter_map(Score::parse).collect();
+        Table { path: path, scores: scores }
+    }
+
+    // Returns the rank of the new score, if it made it into the table.
+    pub fn insert(&mut self, score: Score) -> Option<usize> {
+        let rank = self.scores.iter()
+            .position(|x| x.points < score.points)
+            .unwrap_or(self.scores.len());
+        if rank >= MAX { return None }
+        self.scores.insert(rank, score);
+        self.scores.truncate(MAX);
+        Some (rank)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut file = try!(File::create(&self.path));
+        for score in &self.scores {
+            try!(writeln!(file, "{}", score.to_string()));
+        }
+        Ok (())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::{Score, Table, MAX};
+
+    fn score(points: u64) -> Score {
+        Score { points: points, turns: 10, level: 1, cause: "killed by an enemy".to_string() }
+    }
+
+    #[test]
+    fn parse() {
+        let score = Score::parse("1200\t150\t2\tkilled by an enemy").unwrap();
+        assert_eq!((score.points, score.turns, score.level), (1200, 150, 2));
+        assert_eq!(score.cause, "killed by an enemy");
+        // The cause may hold tabs of its own.
+        assert_eq!(Score::parse("1\t2\t3\ta\tb").unwrap().cause, "a\tb");
+        assert_eq!(Score::parse(&score.to_string()).unwrap().points, 1200);
+        for line in ["", "1\t2\t3", "x\t2\t3\tquit", "1\t-2\t3\tquit"].iter() {
+            assert!(Score::parse(line).is_none(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn insert() {
+        let mut table = Table { path: PathBuf::new(), scores: Vec::new() };
+        assert_eq!(table.insert(score(100)), Some (0));
+        assert_eq!(table.insert(score(300)), Some (0));
+        assert_eq!(table.insert(score(200)), Some (1));
+        // After the scores it ties with.
+        assert_eq!(table.insert(score(200)), Some (2));
+        let points: Vec<u64> = table.scores.iter().map(|x| x.points).collect();
+        assert_eq!(points, vec![300, 200, 200, 100]);
+    }
+
+    #[test]
+    fn truncate() {
+        let mut table = Table { path: PathBuf::new(), scores: Vec::new() };
+        for i in 0..MAX as u64 {
+            assert!(table.insert(score(1000 - i)).is_some());
+        }
+        assert_eq!(table.insert(score(1)), None);
+        assert_eq!(table.insert(score(2000)), Some (0));
+        assert_eq!(table.scores.len(), MAX);
+        assert_eq!(table.scores.last().unwrap().points, 1000 - MAX as u64 + 2);
+    }
+}
@@ -1,30 +1,42 @@
 use std::rc::Rc;
 use na;
-use na::Vec2;
+use na::{Vec2, Vec3};
 use rand;
 use object::{Block, RoleKind, Role};
-use engine::{Sprite, Texture, Manager};
+use engine::{Sprite, Texture, Manager, Sheet};
 
 
 const TILE_SIZE: i32 = 256;
-pub type Offset = Vec2<i32>;
 
 
+// Regions and clips are named in `block.json`.
 pub trait Tile {
-    fn offset(&self) -> Offset;
+    fn region(&self) -> &'static str;
+
+    fn clip(&self) -> Option<&'static str> { None }
 }
 
 
 
 impl Tile for Block {
     #[inline]
-    fn offset(&self) -> Offset {
+    fn region(&self) -> &'static str {
         use object::Block::*;
 
         match *self {
-            Land   => na![0, 0],
-            River  => na![if rand::random() {0} else {1}, 1],
-            Nil    => na![0, 0],
+            Land   => "land",
+            River  => if rand::random() {"river 0"} else {"river 1"},
+            Lava   => if rand::random() {"lava 0"} else {"lava 1"},
+            Wall   => "wall",
+            Nil    => "land",
+        }
+    }
+
+    fn clip(&self) -> Option<&'static str> {
+        match *self {
+            Block::River => Some ("river"),
+            Block::Lava => Some ("lava"),
+            _ => None,
         }
     }
 }
@@ -32,20 +44,33 @@ impl Tile for Block {
 
 impl Tile for RoleKind {
     #[inline]
-    fn offset(&self) -> Offset {
+    fn region(&self) -> &'static str {
+        use object::RoleKind::*;
+
+        match *self {
+            Player => "player",
+            Enemy  => "enemy",
+        }
+    }
+
+    fn clip(&self) -> Option<&'static str> {
         use object::RoleKind::*;
 
         match *self {
-            Player => na![0, 2],
-            Enemy  => na![1, 2],
+            Player => Some ("player-idle"),
+            Enemy  => Some ("enemy-idle"),
         }
     }
 }
 
 
 impl Tile for Role {
-    fn offset(&self) -> Offset {
-        self.kind.offset()
+    fn region(&self) -> &'static str {
+        self.kind.region()
+    }
+
+    fn clip(&self) -> Option<&'static str> {
+        self.kind.clip()
     }
 }
 
@@ -55,46 +80,56 @@ pub struct TileGen {
     pub tile_size: i32,
     pub display_size: i32,
     pub margin: i32,
-    pub texture: Rc<Texture>
+    pub texture: Rc<Texture>,
+    pub sheet: Rc<Sheet>,
 }
 
 
 impl TileGen {
-    #[inline]
-    pub fn horizontal(&self) -> i32 {
-        self.display_size + self.margin
-    }
-
-    #[inline]
-    pub fn vertical(&self) -> i32 {
-        (self.display_size + self.margin) / 2
-    }
-
-    pub fn new(manager: &Manager<Texture>) -> TileGen {
-        let tex = manager.get("block.png");
+    pub fn new(textures: &Manager<Texture>, sheets: &Manager<Sheet>) -> TileGen {
+        let sheet = sheets.get_or_placeholder("block.json");
         TileGen {
-            texture: tex.clone(),
+            texture: textures.get_or_placeholder(&sheet.image),
+            sheet: sheet,
             tile_size: TILE_SIZE,
             display_size: 64,
             margin: 8,
         }
     }
 
-    pub fn sprite(&self, offset: Vec2<i32>, (i, j): (i32, i32)) -> Sprite {
+    #[inline]
+    pub fn locate(&self, position: Vec3<i32>) -> (i32, i32) {
+        (-position.x+position.z, -position.y+position.z)
+    }
+
+    pub fn position(&self, (i, j): (i32, i32)) -> Vec2<f32> {
         let size = na![self.display_size, self.display_size] + self.margin;
         let (a, b) = (size.x/2, size.y/4);
         let x = na![a, b]*i;
         let y = na![a, -b]*-j;
-        let tile_size = na![self.tile_size, self.tile_size];
-        Sprite::new(size-self.margin, tile_size, self.texture.clone())
-            .position(na::cast(x+y))
-            .offset(offset * self.tile_size)
+        na::cast(x+y)
     }
-}
-
-
-
-
-
 
+    // The placeholder sheet has no region, the whole placeholder texture is shown.
+    pub fn sprite(&self, region: &str, (i, j): (i32, i32)) -> Sprite {
+        let size = na![self.display_size, self.display_size];
+        let sprite = match Sprite::named(&self.sheet, region, self.texture.clone(), size) {
+            Some (x) => x,
+            None => Sprite::new(size, na![self.tile_size, self.tile_size], self.texture.clone()),
+        };
+        sprite.position(self.position((i, j)))
+    }
 
+    // A sprite of `tile`, its clip starts at a random frame so that
+    // neighbouring tiles do not look the same, but on the start of the frame
+    // so that the tiles of a chunk change frame together, see `TileMap::set`.
+    pub fn tile<T: Tile>(&self, tile: &T, position: (i32, i32)) -> Sprite {
+        let mut sprite = self.sprite(tile.region(), position);
+        if let Some(clip) = tile.clip().and_then(|x| self.sheet.clips.get(x)) {
+            let k = rand::random::<usize>() % clip.frames.len();
+            let from = clip.frames[..k].iter().fold(0, |a, x| a + x.ms);
+            sprite.play(clip, from);
+        }
+        sprite
+    }
+}